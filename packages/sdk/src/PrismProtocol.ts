@@ -222,6 +222,26 @@ export class PrismProtocol {
     );
   }
 
+  /**
+   * Get the program config PDA
+   * 
+   * Every mutating instruction except the protective ones (revocation, freezing a root,
+   * cancelling ownership changes) takes this account so the program can honour a pause.
+   * 
+   * @returns Tuple of [PDA, bump] - The config PDA address and bump seed
+   * 
+   * @example
+   * ```typescript
+   * const [configPDA] = prism.getConfigPDA();
+   * ```
+   */
+  getConfigPDA(): [PublicKey, number] {
+    return PublicKey.findProgramAddressSync(
+      [Buffer.from('config')],
+      this.programId
+    );
+  }
+
   /**
    * Create a root identity for the connected wallet
    * 
@@ -281,11 +301,12 @@ export class PrismProtocol {
 
     try {
       const signature = await this.program.methods
-        .createRootIdentity(privacyLevel)
+        .createRootIdentity(privacyLevel, 0)
         .accounts({
           user: this.wallet.publicKey,
           rootIdentity: rootPDA,
           systemProgram: SystemProgram.programId,
+          config: this.getConfigPDA()[0],
         })
         .rpc();
 
@@ -545,34 +566,53 @@ export class PrismProtocol {
       // Convert commitment from hex string to bytes
       const commitmentBytes = this.hexToBytes(encryptionResult.encryptedData.commitment);
       const rootHashBytes = this.hexToBytes(rootHash);
+      const [configPDA] = this.getConfigPDA();
 
       const signature = await retryWithSimulation(
         () => this.program!.methods
           .createContextEncrypted(
             options.type,
             new BN(maxPerTx.toString()),
+            0,
             Array.from(rootHashBytes),
-            Array.from(commitmentBytes)
+            Array.from(commitmentBytes),
+            null,
+            null,
+            null,
+            null,
+            null,
+            Array(32).fill(0),
+            new BN(0)
           )
           .accounts({
             user: this.wallet.publicKey,
             rootIdentity: rootPDA,
             contextIdentity: contextPDA,
             systemProgram: SystemProgram.programId,
+            config: configPDA,
           })
           .rpc(),
         () => this.program!.methods
           .createContextEncrypted(
             options.type,
             new BN(maxPerTx.toString()),
+            0,
             Array.from(rootHashBytes),
-            Array.from(commitmentBytes)
+            Array.from(commitmentBytes),
+            null,
+            null,
+            null,
+            null,
+            null,
+            Array(32).fill(0),
+            new BN(0)
           )
           .accounts({
             user: this.wallet.publicKey,
             rootIdentity: rootPDA,
             contextIdentity: contextPDA,
             systemProgram: SystemProgram.programId,
+            config: configPDA,
           })
           .simulate(),
         { maxRetries: 3 }
@@ -724,12 +764,23 @@ export class PrismProtocol {
 
     try {
       const signature = await this.program.methods
-        .createContext(options.type, new BN(maxPerTx.toString()))
+        .createContext(
+          options.type,
+          new BN(maxPerTx.toString()),
+          0,
+          null,
+          null,
+          null,
+          null,
+          Array(32).fill(0),
+          new BN(0)
+        )
         .accounts({
           user: this.wallet.publicKey,
           rootIdentity: rootPDA,
           contextIdentity: contextPDA,
           systemProgram: SystemProgram.programId,
+          config: this.getConfigPDA()[0],
         })
         .rpc();
 
@@ -15,6 +15,7 @@ export {
   ContextType,
   type RootIdentity,
   type ContextIdentity,
+  type ProgramConfig,
   type SolvencyProof,
   type PrismConfig
 } from './types';
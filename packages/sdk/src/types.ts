@@ -35,6 +35,14 @@ export interface RootIdentity {
   bump: number;
 }
 
+export interface ProgramConfig {
+  admin: PublicKey;
+  paused: boolean;
+  bump: number;
+  /** Whether optional events such as SpendHook are emitted */
+  verboseEvents: boolean;
+}
+
 export interface ContextIdentity {
   rootIdentity: PublicKey;
   contextType: ContextType;
@@ -1,30 +1,221 @@
+// Anchor's generated CPI wrappers take one argument per instruction argument
+#![cfg_attr(feature = "cpi", allow(clippy::too_many_arguments))]
+
 use anchor_lang::prelude::*;
-use anchor_lang::solana_program::hash::hash;
+use anchor_lang::solana_program::ed25519_program;
+use anchor_lang::solana_program::hash::{hash, hashv};
+use anchor_lang::solana_program::sysvar::instructions::{
+    load_current_index_checked, load_instruction_at_checked,
+};
+use anchor_lang::solana_program::program_pack::Pack;
+use anchor_lang::system_program;
+use anchor_spl::token::spl_token::state::Mint;
 
 declare_id!("DkD3vtS6K8dJFnGmm9X9CphNDU5LYTYyP8Ve5EEVENdu");
 
+/// Emit an event for a context and fold it into the context's running event digest
+macro_rules! emit_context {
+    ($context:expr, $event:expr) => {{
+        let event = $event;
+        $context.fold_event_digest(&anchor_lang::Event::data(&event));
+        emit!(event);
+    }};
+}
+
+/// Fail unless enough owners of a multisig root signed: `user` plus any owner
+/// signers passed in remaining_accounts must reach the threshold.
+/// Single-owner roots always pass (the account constraints already checked `user`).
+macro_rules! require_owner_quorum {
+    ($ctx:expr) => {
+        require!(
+            owner_quorum_met(
+                &$ctx.accounts.root_identity,
+                &$ctx.accounts.user.key(),
+                $ctx.remaining_accounts,
+            ),
+            PrismError::MultisigThresholdNotMet
+        )
+    };
+}
+
+/// Program version reported by get_program_info
+/// Bump this with every incompatible change to accounts, arguments or layouts
+/// v2: config account on creation instructions, initialize_config requires the upgrade
+/// authority, root layout v9, context layout v15, archive seeds include created_at
+pub const PROGRAM_VERSION: u16 = 2;
+
+/// Current RootIdentity layout version; older accounts must go through migrate_account
+/// v2: revoked_count, v3: type_defaults, v4: contexts_merkle_root,
+/// v5: pending_privacy_level + privacy_change_eta + privacy_downgrade_timelock,
+/// v6: last_context_created_at + creation_cooldown, v7: owners + owner_count + threshold,
+/// v8: created_slot, v9: open_contexts
+pub const ROOT_IDENTITY_VERSION: u8 = 9;
+
+/// max_per_transaction sentinel meaning "use the root's default for this context type"
+pub const USE_TYPE_DEFAULT: u64 = u64::MAX;
+
+/// Current ContextIdentity layout version; older accounts must go through migrate_account
+/// v2: last_nonce, v3: commitment_updated_at, v4: parent_context + child_count,
+/// v5: verifier_pubkey, v6: label, v7: epoch_spent + last_epoch + max_per_epoch,
+/// v8: delegate, v9: linkability_score + score_updated_at, v10: min_per_transaction,
+/// v11: spend_count + max_spend_count, v12: pda_seed, v13: burn_proof, v14: created_slot,
+/// v15: live_children
+pub const CONTEXT_IDENTITY_VERSION: u8 = 15;
+
+// Bits returned by validate_state_consistency, one per violated invariant
+/// Encrypted contexts must have a zero root, a root hash and a commitment; plaintext ones none of these
+pub const VIOLATION_ENCRYPTION_FIELDS: u32 = 1 << 0;
+/// Encryption epoch or computation id set on a plaintext context
+pub const VIOLATION_PLAINTEXT_ENCRYPTION_DATA: u32 = 1 << 1;
+/// One-time override amount set without an expiry
+pub const VIOLATION_OVERRIDE: u32 = 1 << 2;
+/// Spend auth code hash and expiry must be set or cleared together
+pub const VIOLATION_AUTH_CODE: u32 = 1 << 3;
+/// Event tag stored as Some(0) instead of None
+pub const VIOLATION_EVENT_TAG: u32 = 1 << 4;
+/// Reserved space above MAX_RESERVED_SPACE
+pub const VIOLATION_RESERVED_SPACE: u32 = 1 << 5;
+
+/// Maximum extra bytes a context can reserve beyond ContextIdentity::SIZE at creation
+pub const MAX_RESERVED_SPACE: u16 = 1024;
+
+/// Maximum number of contexts processed by a single batch instruction (compute budget)
+pub const MAX_BATCH_SIZE: usize = 10;
+
+/// Maximum number of candidate roots in a ring membership proof
+pub const MAX_RING_SIZE: usize = 32;
+
+/// Maximum Merkle proof length for context inclusion (enough for u16::MAX contexts)
+pub const MAX_MERKLE_DEPTH: usize = 16;
+
+/// Domain tags separating Merkle leaves from interior nodes
+pub const CONTEXT_LEAF_DOMAIN: &[u8] = b"prism:context-leaf:v1";
+pub const CONTEXT_NODE_DOMAIN: &[u8] = b"prism:context-node:v1";
+
+/// Domain tag prepended to the root identity hash preimage
+pub const ROOT_IDENTITY_HASH_DOMAIN: &[u8] = b"prism:root-identity:v1";
+
+// Actions reported in EmergencyAction events
+/// Root frozen, halting spending on all its contexts
+pub const EMERGENCY_ACTION_FREEZE: u8 = 0;
+/// Root unfrozen
+pub const EMERGENCY_ACTION_UNFREEZE: u8 = 1;
+/// Guardian recovery started
+pub const EMERGENCY_ACTION_RECOVERY_INITIATED: u8 = 2;
+/// Guardian recovery completed, ownership moved
+pub const EMERGENCY_ACTION_RECOVERY_EXECUTED: u8 = 3;
+
+/// Maximum number of recovery guardians per root
+pub const MAX_GUARDIANS: usize = 3;
+
+/// Delay between a guardian recovery starting and it being executable,
+/// giving the current owner time to cancel
+pub const RECOVERY_TIMELOCK: i64 = 2 * 24 * 60 * 60; // 48 hours
+
+/// PDA prefix of contexts derived from their encryption commitment rather than
+/// the root key; kept apart from b"context" so the two address spaces can't collide
+pub const ENCRYPTED_CONTEXT_SEED: &[u8] = b"enc_context";
+
+/// Archive counterpart of ENCRYPTED_CONTEXT_SEED: archives of pda_seed-derived
+/// contexts are keyed by the pda_seed, never by the root
+pub const ENCRYPTED_ARCHIVE_SEED: &[u8] = b"enc_archive";
+
+/// Maximum number of owners of a multisig root
+pub const MAX_OWNERS: usize = 5;
+
+/// Delay before a privacy downgrade takes effect when the root hasn't set its own
+pub const DEFAULT_PRIVACY_DOWNGRADE_TIMELOCK: i64 = 24 * 60 * 60; // 24 hours
+
+/// Shortest privacy downgrade delay a root may configure
+pub const MIN_PRIVACY_DOWNGRADE_TIMELOCK: i64 = 60 * 60; // 1 hour
+
+// Weakest-link caps used by compute_effective_privacy. Privacy levels run from
+// 0 (Maximum) to 4 (Public), so a cap is the strongest level still reachable
+// once any active (unrevoked, unexpired) context has the given property.
+/// Cap when an active context stores its root identity in plaintext
+pub const PLAINTEXT_CONTEXT_PRIVACY_CAP: u8 = 2;
+/// Cap when an active context is of the Public type (5)
+pub const PUBLIC_CONTEXT_PRIVACY_CAP: u8 = 4;
+
+/// Context types a root may create at each privacy level, as bitmasks over
+/// context_type (bit n = type n), indexed by privacy level 0=Maximum .. 4=Public.
+/// Mirrors the effective-privacy caps: plaintext contexts need at least Medium
+/// (PLAINTEXT_CONTEXT_PRIVACY_CAP) and Public contexts need a Public root.
+///
+/// | level   | encrypted         | plaintext         |
+/// |---------|-------------------|-------------------|
+/// | Maximum | all but Public    | none              |
+/// | High    | all but Public    | none              |
+/// | Medium  | all but Public    | all but Public    |
+/// | Low     | all but Public    | all but Public    |
+/// | Public  | all               | all               |
+pub const ENCRYPTED_CONTEXT_POLICY: [u8; 5] = [0b01_1111, 0b01_1111, 0b01_1111, 0b01_1111, 0b11_1111];
+pub const PLAINTEXT_CONTEXT_POLICY: [u8; 5] = [0b00_0000, 0b00_0000, 0b01_1111, 0b01_1111, 0b11_1111];
+
+/// Linkability score lost per second of inactivity
+pub const LINKABILITY_DECAY_PER_SECOND: u64 = 1;
+/// Spend amount (in the context's units) that adds one point of linkability
+pub const LINKABILITY_AMOUNT_UNIT: u64 = 1_000_000;
+
 #[program]
 pub mod prism {
     use super::*;
 
     /// Create a root identity for a user (one per wallet)
     /// This is the master identity that owns all contexts
+    /// `max_contexts` caps how many contexts it can ever create (0 = u16::MAX)
     pub fn create_root_identity(
         ctx: Context<CreateRootIdentity>,
         privacy_level: u8,
+        max_contexts: u16,
+    ) -> Result<()> {
+        init_root_identity(
+            &mut ctx.accounts.root_identity,
+            ctx.accounts.user.key(),
+            ctx.bumps.root_identity,
+            privacy_level,
+            max_contexts,
+        )
+    }
+
+    /// Create a root identity controlled by several owners (one of them the signer)
+    /// Owner-gated instructions then need `threshold` distinct owners to sign: the
+    /// `user` account plus co-signing owners appended to remaining_accounts.
+    /// Transferring or recovering ownership turns it back into a single-owner root.
+    /// Any single owner can still freeze_root and cancel a pending ownership transfer,
+    /// recovery or privacy downgrade: each only blocks a change and a quorum can undo it.
+    pub fn create_multisig_root(
+        ctx: Context<CreateRootIdentity>,
+        privacy_level: u8,
+        max_contexts: u16,
+        owners: Vec<Pubkey>,
+        threshold: u8,
     ) -> Result<()> {
-        require!(privacy_level <= 4, PrismError::InvalidPrivacyLevel);
+        let user = ctx.accounts.user.key();
+        let distinct = owners.iter().enumerate().all(|(i, owner)| !owners[..i].contains(owner));
+        require!(
+            owners.len() >= 2
+                && owners.len() <= MAX_OWNERS
+                && distinct
+                && !owners.contains(&Pubkey::default())
+                && owners.contains(&user)
+                && threshold >= 1
+                && threshold as usize <= owners.len(),
+            PrismError::InvalidMultisigConfig
+        );
         
         let root = &mut ctx.accounts.root_identity;
-        root.owner = ctx.accounts.user.key();
-        root.created_at = Clock::get()?.unix_timestamp;
-        root.privacy_level = privacy_level;
-        root.context_count = 0;
-        root.bump = ctx.bumps.root_identity;
+        init_root_identity(root, user, ctx.bumps.root_identity, privacy_level, max_contexts)?;
         
-        emit!(RootIdentityCreated {
-            owner: root.owner,
-            privacy_level,
+        root.owners[..owners.len()].copy_from_slice(&owners);
+        root.owner_count = owners.len() as u8;
+        root.threshold = threshold;
+        
+        emit!(MultisigConfigured {
+            root_identity: root.key(),
+            owners: root.owners,
+            owner_count: root.owner_count,
+            threshold,
             timestamp: root.created_at,
         });
         
@@ -33,191 +224,313 @@ pub mod prism {
 
     /// Create a new context (disposable identity) linked to root
     /// Used for dark pool trading, DeFi, etc.
+    /// `reserved_space` pads the account so future fields fit without a realloc
+    /// `mint` denominates the limits in an SPL token instead of lamports
+    /// `allowed_program` restricts spending to a single target program
+    /// A `max_per_transaction` of USE_TYPE_DEFAULT takes the root's default for `context_type`
+    /// `label` is a zero-padded UTF-8 name for client-side organization (all zero = none)
+    /// `min_per_transaction` rejects dust spends used to probe the context (0 = no floor)
+    /// The new context's address and index are returned as program return data
+    #[allow(clippy::too_many_arguments)]
     pub fn create_context(
         ctx: Context<CreateContext>,
         context_type: u8,
         max_per_transaction: u64,
-    ) -> Result<()> {
-        require!(context_type <= 5, PrismError::InvalidContextType);
-        
-        let context = &mut ctx.accounts.context_identity;
-        let root = &mut ctx.accounts.root_identity;
-        
-        context.root_identity = root.key();
-        context.root_identity_hash = None;
-        context.encryption_commitment = None;
-        context.context_type = context_type;
-        context.created_at = Clock::get()?.unix_timestamp;
-        context.max_per_transaction = max_per_transaction;
-        context.total_spent = 0;
-        context.revoked = false;
-        context.context_index = root.context_count;
-        context.bump = ctx.bumps.context_identity;
-        
-        root.context_count = root.context_count.checked_add(1).unwrap();
-        
-        emit!(ContextCreated {
-            root_identity: root.key(),
-            context_identity: context.key(),
+        reserved_space: u16,
+        event_tag: Option<u32>,
+        expires_at: Option<i64>,
+        mint: Option<Pubkey>,
+        allowed_program: Option<Pubkey>,
+        label: [u8; 32],
+        min_per_transaction: u64,
+    ) -> Result<CreatedContext> {
+        require_owner_quorum!(ctx);
+        // Anchor serializes the returned value with set_return_data
+        init_context(
+            &mut ctx.accounts.root_identity,
+            &mut ctx.accounts.context_identity,
+            ctx.bumps.context_identity,
             context_type,
             max_per_transaction,
-            context_index: context.context_index,
-            timestamp: context.created_at,
-        });
-        
-        Ok(())
+            reserved_space,
+            event_tag,
+            expires_at,
+            mint,
+            allowed_program,
+            label,
+            min_per_transaction,
+            None,
+        )
     }
 
     /// Create a context with encrypted root identity for enhanced privacy
     /// The root identity PDA is encrypted with Arcium MPC and stored as a hash
     /// This prevents linking multiple contexts together (they all have encrypted root_identity)
+    /// The PDA is `[ENCRYPTED_CONTEXT_SEED, encryption_commitment, index]` instead of the
+    /// root key, so the address can't be recomputed from the root. The root identity hash
+    /// wouldn't do: it is a public function of the root key. The separate prefix keeps a
+    /// chosen commitment from squatting another root's next plaintext context address.
+    /// The new context's address and index are returned as program return data
+    #[allow(clippy::too_many_arguments)]
     pub fn create_context_encrypted(
-        ctx: Context<CreateContext>,
+        ctx: Context<CreateEncryptedContext>,
         context_type: u8,
         max_per_transaction: u64,
+        reserved_space: u16,
         root_identity_hash: [u8; 32],
         encryption_commitment: [u8; 32],
-    ) -> Result<()> {
-        require!(context_type <= 5, PrismError::InvalidContextType);
-        
-        let context = &mut ctx.accounts.context_identity;
-        let root = &mut ctx.accounts.root_identity;
+        computation_id: Option<[u8; 32]>,
+        event_tag: Option<u32>,
+        expires_at: Option<i64>,
+        mint: Option<Pubkey>,
+        allowed_program: Option<Pubkey>,
+        label: [u8; 32],
+        min_per_transaction: u64,
+    ) -> Result<CreatedContext> {
+        require_owner_quorum!(ctx);
         
         // Verify the hash matches the root identity PDA (what's stored in context)
         // This ensures the root identity is properly encrypted
-        let computed_hash = hash_root_identity(&root.key());
         require!(
-            computed_hash == root_identity_hash,
+            hash_root_identity(&ctx.accounts.root_identity.key()) == root_identity_hash,
             PrismError::InvalidRootHash
         );
         
-        // Store ONLY encrypted/hashed root identity (no plaintext for privacy)
-        // The root_identity field is set to a zero pubkey to indicate it's encrypted
-        // All verification uses root_identity_hash instead
-        // Explicitly set to zero pubkey (all zeros in bytes)
-        let zero_pubkey = Pubkey::new_from_array([0u8; 32]);
-        context.root_identity = zero_pubkey; // Zero pubkey = encrypted context
-        context.root_identity_hash = Some(root_identity_hash); // Hash of root identity PDA (from Arcium)
-        context.encryption_commitment = Some(encryption_commitment);
-        context.context_type = context_type;
-        context.created_at = Clock::get()?.unix_timestamp;
-        context.max_per_transaction = max_per_transaction;
-        context.total_spent = 0;
-        context.revoked = false;
-        context.context_index = root.context_count;
-        context.bump = ctx.bumps.context_identity;
-        
-        root.context_count = root.context_count.checked_add(1).unwrap();
-        
-        emit!(ContextCreated {
-            root_identity: root.key(),
-            context_identity: context.key(),
+        init_context(
+            &mut ctx.accounts.root_identity,
+            &mut ctx.accounts.context_identity,
+            ctx.bumps.context_identity,
             context_type,
             max_per_transaction,
-            context_index: context.context_index,
-            timestamp: context.created_at,
-        });
-        
-        Ok(())
+            reserved_space,
+            event_tag,
+            expires_at,
+            mint,
+            allowed_program,
+            label,
+            min_per_transaction,
+            Some(ContextEncryption {
+                root_identity_hash,
+                commitment: encryption_commitment,
+                computation_id,
+            }),
+        )
     }
 
     /// Verify an Arcium encryption commitment
-    /// This can be called on-chain to verify commitments without decrypting
+    /// The preceding instruction must be an Ed25519 signature by the context's
+    /// verifier key over commitment_proof_message(context, commitment, binding_key);
+    /// the runtime checks the signature, we check signer and message.
+    /// The result is returned as program return data, so CPI callers can read it
+    /// with `get_return_data` (or the `Return<CommitmentVerification>` from `prism::cpi`)
     pub fn verify_commitment(
         ctx: Context<VerifyCommitment>,
         commitment: [u8; 32],
         binding_key: Pubkey,
-    ) -> Result<bool> {
-        // Verify commitment format (64 hex chars = 32 bytes)
-        // In production, this would verify against stored commitment
-        let context = &ctx.accounts.context_identity;
+    ) -> Result<CommitmentVerification> {
+        let (signer, message) = load_ed25519_signature(&ctx.accounts.instructions)?;
+        let is_valid = commitment_proof_valid(
+            &ctx.accounts.context_identity,
+            &commitment,
+            &binding_key,
+            &signer,
+            &message,
+        );
         
-        if let Some(stored_commitment) = context.encryption_commitment {
-            // Verify commitment matches and binding key matches context
-            let is_valid = stored_commitment == commitment 
-                && binding_key == context.key();
-            
-            Ok(is_valid)
-        } else {
-            // No commitment stored, cannot verify
-            Ok(false)
-        }
+        // Anchor serializes the returned value with set_return_data
+        Ok(CommitmentVerification { is_valid, binding_key })
     }
 
     /// Revoke a context (burn disposable identity after use)
     /// Used after dark pool trade to eliminate trace
-    pub fn revoke_context(ctx: Context<RevokeContext>) -> Result<()> {
+    /// Sub-contexts passed in remaining_accounts are revoked along with it
+    pub fn revoke_context<'info>(
+        ctx: Context<'_, '_, 'info, 'info, RevokeContext<'info>>,
+    ) -> Result<()> {
+        require_owner_quorum!(ctx);
+        let root = &mut ctx.accounts.root_identity;
         let context = &mut ctx.accounts.context_identity;
         
+        // Signers are multisig co-signers, not sub-contexts
+        let children: Vec<&AccountInfo<'info>> = ctx.remaining_accounts
+            .iter()
+            .filter(|account| !account.is_signer)
+            .collect();
         require!(!context.revoked, PrismError::ContextAlreadyRevoked);
+        require!(children.len() <= MAX_BATCH_SIZE, PrismError::BatchTooLarge);
         
+        let now = Clock::get()?.unix_timestamp;
+        let burn_proof = burn_proof_hash(&context.key(), context.total_spent, now);
         context.revoked = true;
+        context.revoked_at = now;
+        context.burn_proof = Some(burn_proof);
+        root.revoked_count = root.revoked_count.saturating_add(1);
+        
+        // Cascade to children; they don't count against the root's context_count
+        let parent_key = context.key();
+        let mut children_revoked: u8 = 0;
+        for account_info in children.iter().copied() {
+            let mut child: Account<ContextIdentity> = Account::try_from(account_info)?;
+            require!(child.version == CONTEXT_IDENTITY_VERSION, PrismError::UnsupportedVersion);
+            require!(
+                sub_context_belongs_to_parent(&child, &parent_key),
+                PrismError::ContextMismatch
+            );
+            if child.revoked {
+                continue;
+            }
+            
+            child.revoked = true;
+            child.revoked_at = now;
+            child.burn_proof = Some(burn_proof_hash(&child.key(), child.total_spent, now));
+            child.exit(&crate::ID)?;
+            children_revoked += 1;
+        }
+        context.live_children = context.live_children.saturating_sub(children_revoked as u16);
         
         // For encrypted contexts, root_identity is zero pubkey (privacy)
-        emit!(ContextRevoked {
+        emit_context!(context, ContextRevoked {
             root_identity: context.root_identity, // May be zero for encrypted contexts
             context_identity: context.key(),
             context_type: context.context_type,
             total_spent: context.total_spent,
-            timestamp: Clock::get()?.unix_timestamp,
+            context_count: root.context_count,
+            revoked_count: root.revoked_count,
+            event_tag: context.event_tag,
+            burn_proof,
+            timestamp: now,
         });
+        if children_revoked > 0 {
+            emit_context!(context, SubContextsRevoked {
+                parent_context: parent_key,
+                count: children_revoked,
+                event_tag: context.event_tag,
+                timestamp: now,
+            });
+        }
         
         Ok(())
     }
 
     /// Check if a transaction amount is within context spending limits
     /// Called before executing trades in dark pools
+    ///
+    /// Other programs can CPI into this through the client Anchor generates when
+    /// prism is a dependency with `features = ["cpi"]`:
+    ///
+    /// ```ignore
+    /// let cpi_ctx = CpiContext::new(
+    ///     ctx.accounts.prism_program.to_account_info(),
+    ///     prism::cpi::accounts::CheckSpendingLimit {
+    ///         context_identity: ctx.accounts.context_identity.to_account_info(),
+    ///         root_identity: ctx.accounts.root_identity.to_account_info(),
+    ///         user: ctx.accounts.user.to_account_info(),
+    ///     },
+    /// );
+    /// prism::cpi::check_spending_limit(cpi_ctx, amount, crate::ID)?;
+    /// ```
+    ///
+    /// The call fails with the matching PrismError when the spend isn't allowed.
     pub fn check_spending_limit(
         ctx: Context<CheckSpendingLimit>,
         amount: u64,
+        target_program: Pubkey,
     ) -> Result<()> {
         let context = &ctx.accounts.context_identity;
         
-        require!(!context.revoked, PrismError::ContextRevoked);
+        require!(!ctx.accounts.root_identity.frozen, PrismError::RootFrozen);
+        check_context_spend(context, amount, &target_program, &Clock::get()?)?;
         require!(
-            amount <= context.max_per_transaction,
-            PrismError::ExceedsTransactionLimit
+            context.mint.is_some() || ctx.accounts.root_identity.global_allows(amount),
+            PrismError::ExceedsGlobalLimit
         );
         
         Ok(())
     }
 
     /// Record spending against a context (for tracking limits)
+    /// Spends above the auth code threshold must supply the current code's preimage
+    /// If `expected_amount` is given, `amount` must be within `max_slippage_bps` of it
+    /// `hook_data` is echoed in a SpendHook event for off-chain listeners while the
+    /// program config has verbose events enabled
+    /// `target_program` is the program the spend goes to, checked against the context's allowlist
+    /// A nonzero `nonce` must exceed the last one used, so rebroadcasts are rejected (0 = unchecked)
+    /// Signed by the root owner or the context's delegate
+    #[allow(clippy::too_many_arguments)]
     pub fn record_spending(
         ctx: Context<RecordSpending>,
         amount: u64,
+        auth_code: Option<Vec<u8>>,
+        expected_amount: Option<u64>,
+        max_slippage_bps: Option<u16>,
+        hook_data: Option<[u8; 32]>,
+        target_program: Pubkey,
+        nonce: u64,
     ) -> Result<()> {
-        let context = &mut ctx.accounts.context_identity;
-        
-        require!(!context.revoked, PrismError::ContextRevoked);
-        require!(
-            amount <= context.max_per_transaction,
-            PrismError::ExceedsTransactionLimit
-        );
-        
-        context.total_spent = context.total_spent.checked_add(amount)
-            .ok_or(PrismError::SpendingOverflow)?;
-        
-        emit!(SpendingRecorded {
-            context_identity: context.key(),
+        // Delegates act within the context's limits without the owners' quorum
+        if ctx.accounts.context_identity.delegate != Some(ctx.accounts.user.key()) {
+            require_owner_quorum!(ctx);
+        }
+        apply_spending(
+            &mut ctx.accounts.root_identity,
+            &mut ctx.accounts.context_identity,
             amount,
-            total_spent: context.total_spent,
-            timestamp: Clock::get()?.unix_timestamp,
-        });
-        
-        Ok(())
+            auth_code,
+            expected_amount,
+            max_slippage_bps,
+            hook_data.filter(|_| ctx.accounts.config.verbose_events),
+            target_program,
+            nonce,
+        )
+    }
+
+    /// Record spending against a token-denominated context
+    /// Same checks as record_spending, but the mint must match the context's configured mint
+    #[allow(clippy::too_many_arguments)]
+    pub fn record_spending_token(
+        ctx: Context<RecordSpendingToken>,
+        amount: u64,
+        auth_code: Option<Vec<u8>>,
+        expected_amount: Option<u64>,
+        max_slippage_bps: Option<u16>,
+        hook_data: Option<[u8; 32]>,
+        target_program: Pubkey,
+        nonce: u64,
+    ) -> Result<()> {
+        // Delegates act within the context's limits without the owners' quorum
+        if ctx.accounts.context_identity.delegate != Some(ctx.accounts.user.key()) {
+            require_owner_quorum!(ctx);
+        }
+        apply_spending(
+            &mut ctx.accounts.root_identity,
+            &mut ctx.accounts.context_identity,
+            amount,
+            auth_code,
+            expected_amount,
+            max_slippage_bps,
+            hook_data.filter(|_| ctx.accounts.config.verbose_events),
+            target_program,
+            nonce,
+        )
     }
 
     /// Update privacy level for root identity
+    /// Only raising privacy (a lower number) is instant, and it drops any pending
+    /// downgrade; lowering it must go through propose/finalize_privacy_change.
     pub fn update_privacy_level(
         ctx: Context<UpdatePrivacyLevel>,
         new_privacy_level: u8,
     ) -> Result<()> {
+        require_owner_quorum!(ctx);
         require!(new_privacy_level <= 4, PrismError::InvalidPrivacyLevel);
         
         let root = &mut ctx.accounts.root_identity;
         let old_level = root.privacy_level;
+        require!(new_privacy_level <= old_level, PrismError::PrivacyDowngradeTimelocked);
+        
         root.privacy_level = new_privacy_level;
+        root.pending_privacy_level = None;
+        root.privacy_change_eta = 0;
         
         emit!(PrivacyLevelUpdated {
             root_identity: root.key(),
@@ -228,281 +541,5724 @@ pub mod prism {
         
         Ok(())
     }
-}
 
-// ============================================================================
-// ACCOUNT CONTEXTS
-// ============================================================================
+    /// Permanently seal a context's configuration
+    /// Limits and settings can no longer change; spending and revocation still work
+    pub fn seal_context(ctx: Context<SealContext>) -> Result<()> {
+        require_owner_quorum!(ctx);
+        let context = &mut ctx.accounts.context_identity;
+        
+        require!(!context.config_sealed, PrismError::ContextSealed);
+        
+        context.config_sealed = true;
+        
+        emit_context!(context, ContextSealed {
+            context_identity: context.key(),
+            event_tag: context.event_tag,
+            timestamp: Clock::get()?.unix_timestamp,
+        });
+        
+        Ok(())
+    }
 
-#[derive(Accounts)]
-pub struct CreateRootIdentity<'info> {
-    #[account(mut)]
-    pub user: Signer<'info>,
-    
-    #[account(
-        init,
-        payer = user,
-        space = RootIdentity::SIZE,
-        seeds = [b"root", user.key().as_ref()],
-        bump
-    )]
-    pub root_identity: Account<'info, RootIdentity>,
-    
-    pub system_program: Program<'info, System>,
-}
+    /// Start a new encryption epoch for the root identity
+    /// Encrypted contexts created afterwards can't be grouped with earlier ones by epoch
+    pub fn rotate_encryption_epoch(ctx: Context<RotateEncryptionEpoch>) -> Result<()> {
+        require_owner_quorum!(ctx);
+        let root = &mut ctx.accounts.root_identity;
+        let old_epoch = root.encryption_epoch;
+        root.encryption_epoch = old_epoch.checked_add(1)
+            .ok_or(PrismError::EpochOverflow)?;
+        
+        emit!(EncryptionEpochRotated {
+            root_identity: root.key(),
+            old_epoch,
+            new_epoch: root.encryption_epoch,
+            timestamp: Clock::get()?.unix_timestamp,
+        });
+        
+        Ok(())
+    }
 
-#[derive(Accounts)]
-pub struct CreateContext<'info> {
-    #[account(mut)]
-    pub user: Signer<'info>,
-    
-    #[account(
-        mut,
-        seeds = [b"root", user.key().as_ref()],
-        bump = root_identity.bump,
-        constraint = root_identity.owner == user.key() @ PrismError::Unauthorized
-    )]
-    pub root_identity: Account<'info, RootIdentity>,
-    
-    #[account(
-        init,
-        payer = user,
-        space = ContextIdentity::SIZE,
-        seeds = [
-            b"context",
-            root_identity.key().as_ref(),
-            &root_identity.context_count.to_le_bytes()
-        ],
-        bump
-    )]
-    pub context_identity: Account<'info, ContextIdentity>,
-    
-    pub system_program: Program<'info, System>,
-}
+    /// Check whether two encrypted contexts were created in the same encryption epoch
+    /// Lets a user prove deliberate grouping without revealing the root identity
+    pub fn verify_same_epoch(ctx: Context<VerifySameEpoch>) -> Result<bool> {
+        let context_a = &ctx.accounts.context_a;
+        let context_b = &ctx.accounts.context_b;
+        
+        // Only encrypted contexts carry a meaningful epoch
+        match (context_a.root_identity_hash, context_b.root_identity_hash) {
+            (Some(hash_a), Some(hash_b)) => Ok(
+                hash_a == hash_b && context_a.encryption_epoch == context_b.encryption_epoch
+            ),
+            _ => Ok(false),
+        }
+    }
 
-#[derive(Accounts)]
-pub struct RevokeContext<'info> {
-    #[account(mut)]
-    pub user: Signer<'info>,
-    
-    #[account(
-        seeds = [b"root", user.key().as_ref()],
-        bump = root_identity.bump,
-        constraint = root_identity.owner == user.key() @ PrismError::Unauthorized
-    )]
-    pub root_identity: Account<'info, RootIdentity>,
-    
-    #[account(
-        mut,
-        seeds = [
-            b"context",
-            root_identity.key().as_ref(),
-            &context_identity.context_index.to_le_bytes()
-        ],
-        bump = context_identity.bump
-    )]
-    pub context_identity: Account<'info, ContextIdentity>,
-}
+    /// Commit the root identity to a privacy policy by storing its hash
+    pub fn set_privacy_policy(
+        ctx: Context<SetPrivacyPolicy>,
+        policy_hash: [u8; 32],
+    ) -> Result<()> {
+        require_owner_quorum!(ctx);
+        let root = &mut ctx.accounts.root_identity;
+        root.privacy_policy_hash = Some(policy_hash);
+        
+        emit!(PrivacyPolicySet {
+            root_identity: root.key(),
+            policy_hash,
+            timestamp: Clock::get()?.unix_timestamp,
+        });
+        
+        Ok(())
+    }
 
-#[derive(Accounts)]
-pub struct VerifyCommitment<'info> {
-    #[account(
-        seeds = [
-            b"context",
-            // For encrypted contexts, derive from root_identity account instead
-            // This requires passing root_identity as a separate account
-            root_identity.key().as_ref(),
-            &context_identity.context_index.to_le_bytes()
-        ],
-        bump = context_identity.bump
-    )]
-    pub context_identity: Account<'info, ContextIdentity>,
-    
-    // Need root_identity account to derive PDA for encrypted contexts
-    #[account(
-        seeds = [b"root", user.key().as_ref()],
-        bump = root_identity.bump
-    )]
-    pub root_identity: Account<'info, RootIdentity>,
-    
-    pub user: Signer<'info>,
-}
+    /// Check that a policy document matches the root's committed policy hash
+    /// Anyone can call this; returns false if no policy has been committed
+    pub fn verify_privacy_policy(
+        ctx: Context<VerifyPrivacyPolicy>,
+        policy_document: Vec<u8>,
+    ) -> Result<bool> {
+        let root = &ctx.accounts.root_identity;
+        
+        if let Some(stored_hash) = root.privacy_policy_hash {
+            Ok(hash(&policy_document).to_bytes() == stored_hash)
+        } else {
+            Ok(false)
+        }
+    }
 
-#[derive(Accounts)]
-pub struct CheckSpendingLimit<'info> {
-    #[account(
-        seeds = [
-            b"context",
-            root_identity.key().as_ref(),
-            &context_identity.context_index.to_le_bytes()
-        ],
-        bump = context_identity.bump
-    )]
-    pub context_identity: Account<'info, ContextIdentity>,
-    
-    // Need root_identity account to derive PDA for encrypted contexts
-    #[account(
-        seeds = [b"root", user.key().as_ref()],
-        bump = root_identity.bump
-    )]
-    pub root_identity: Account<'info, RootIdentity>,
-    
-    pub user: Signer<'info>,
-}
+    /// Authorize a single spend above max_per_transaction, up to `amount`
+    /// The override is consumed by the next spend that needs it, or lapses at `expires_at`
+    pub fn authorize_override(
+        ctx: Context<AuthorizeOverride>,
+        amount: u64,
+        expires_at: i64,
+    ) -> Result<()> {
+        require_owner_quorum!(ctx);
+        let context = &mut ctx.accounts.context_identity;
+        let now = Clock::get()?.unix_timestamp;
+        
+        require!(!context.revoked, PrismError::ContextRevoked);
+        require!(!context.config_sealed, PrismError::ContextSealed);
+        require!(expires_at > now, PrismError::InvalidOverrideExpiry);
+        
+        context.one_time_override = amount;
+        context.override_expires_at = expires_at;
+        
+        emit_context!(context, OverrideAuthorized {
+            context_identity: context.key(),
+            amount,
+            expires_at,
+            event_tag: context.event_tag,
+            timestamp: now,
+        });
+        
+        Ok(())
+    }
+
+    /// Attest that a context's total spending is below `threshold`
+    /// Only the boolean result and threshold are emitted, never the actual total
+    pub fn attest_spending_below(
+        ctx: Context<AttestSpending>,
+        threshold: u64,
+    ) -> Result<bool> {
+        require_owner_quorum!(ctx);
+        let context = &ctx.accounts.context_identity;
+        let below = context.total_spent < threshold;
+        
+        emit!(SpendingAttested {
+            context_identity: context.key(),
+            threshold,
+            below,
+            event_tag: context.event_tag,
+            timestamp: Clock::get()?.unix_timestamp,
+        });
+        
+        Ok(below)
+    }
+
+    /// Move a batch of encrypted contexts to the root's current encryption epoch
+    /// Contexts are passed via remaining_accounts and must all belong to this root
+    pub fn migrate_contexts_to_epoch<'info>(
+        ctx: Context<'_, '_, 'info, 'info, MigrateContextsToEpoch<'info>>,
+    ) -> Result<()> {
+        require_owner_quorum!(ctx);
+        let root = &ctx.accounts.root_identity;
+        let root_key = root.key();
+        let epoch = root.encryption_epoch;
+        
+        // Signers are multisig co-signers, not contexts
+        let contexts: Vec<&AccountInfo<'info>> = ctx.remaining_accounts
+            .iter()
+            .filter(|account| !account.is_signer)
+            .collect();
+        require!(contexts.len() <= MAX_BATCH_SIZE, PrismError::BatchTooLarge);
+        
+        for account_info in contexts.iter().copied() {
+            let mut context: Account<ContextIdentity> = Account::try_from(account_info)?;
+            require!(context.version == CONTEXT_IDENTITY_VERSION, PrismError::UnsupportedVersion);
+            
+            require!(
+                context_belongs_to_root(&context, &root_key),
+                PrismError::ContextMismatch
+            );
+            require!(
+                context.root_identity_hash.is_some(),
+                PrismError::ContextNotEncrypted
+            );
+            require!(!context.config_sealed, PrismError::ContextSealed);
+            
+            context.encryption_epoch = epoch;
+            context.exit(&crate::ID)?;
+        }
+        
+        emit!(ContextsMigratedToEpoch {
+            root_identity: root_key,
+            epoch,
+            count: contexts.len() as u8,
+            timestamp: Clock::get()?.unix_timestamp,
+        });
+        
+        Ok(())
+    }
+
+    /// Verify an encrypted context was produced by a specific Arcium MPC computation
+    pub fn verify_computation_binding(
+        ctx: Context<VerifyComputationBinding>,
+        computation_id: [u8; 32],
+    ) -> Result<()> {
+        let context = &ctx.accounts.context_identity;
+        
+        require!(
+            context.computation_id == Some(computation_id),
+            PrismError::ComputationMismatch
+        );
+        
+        Ok(())
+    }
+
+    /// Set a single-use auth code (by hash) required for spends above `threshold`
+    /// The code itself is delivered out-of-band; a threshold of 0 disables the requirement
+    pub fn set_spend_auth_code(
+        ctx: Context<SetSpendAuthCode>,
+        code_hash: [u8; 32],
+        expires_at: i64,
+        threshold: u64,
+    ) -> Result<()> {
+        require_owner_quorum!(ctx);
+        let context = &mut ctx.accounts.context_identity;
+        let now = Clock::get()?.unix_timestamp;
+        
+        require!(!context.revoked, PrismError::ContextRevoked);
+        require!(!context.config_sealed, PrismError::ContextSealed);
+        require!(expires_at > now, PrismError::InvalidAuthCodeExpiry);
+        
+        context.spend_auth_code_hash = Some(code_hash);
+        context.auth_code_expires_at = expires_at;
+        context.auth_code_threshold = threshold;
+        
+        emit_context!(context, SpendAuthCodeSet {
+            context_identity: context.key(),
+            threshold,
+            expires_at,
+            event_tag: context.event_tag,
+            timestamp: now,
+        });
+        
+        Ok(())
+    }
+
+    /// Derive the per-counterparty payment address for a context
+    /// Each counterparty pays into its own PDA so receiving addresses are never reused
+    pub fn get_counterparty_payment_address(
+        ctx: Context<GetCounterpartyPaymentAddress>,
+        counterparty: Pubkey,
+    ) -> Result<Pubkey> {
+        let (payment_address, _bump) = Pubkey::find_program_address(
+            &[
+                b"pay",
+                ctx.accounts.context_identity.key().as_ref(),
+                counterparty.as_ref(),
+            ],
+            ctx.program_id,
+        );
+        
+        Ok(payment_address)
+    }
+
+    /// Sweep all lamports received at a counterparty payment address to the root owner
+    pub fn sweep_counterparty_payment(
+        ctx: Context<SweepCounterpartyPayment>,
+        counterparty: Pubkey,
+    ) -> Result<()> {
+        require_owner_quorum!(ctx);
+        let amount = ctx.accounts.payment_address.lamports();
+        require!(amount > 0, PrismError::NothingToSweep);
+        
+        let context_key = ctx.accounts.context_identity.key();
+        let bump = [ctx.bumps.payment_address];
+        let signer_seeds: &[&[&[u8]]] = &[&[
+            b"pay",
+            context_key.as_ref(),
+            counterparty.as_ref(),
+            &bump,
+        ]];
+        
+        system_program::transfer(
+            CpiContext::new_with_signer(
+                ctx.accounts.system_program.to_account_info(),
+                system_program::Transfer {
+                    from: ctx.accounts.payment_address.to_account_info(),
+                    to: ctx.accounts.user.to_account_info(),
+                },
+                signer_seeds,
+            ),
+            amount,
+        )?;
+        
+        // Counterparty is deliberately left out of the event to keep relationships private
+        emit!(CounterpartyPaymentSwept {
+            context_identity: context_key,
+            amount,
+            event_tag: ctx.accounts.context_identity.event_tag,
+            timestamp: Clock::get()?.unix_timestamp,
+        });
+        
+        Ok(())
+    }
+
+    /// Check that a batch of contexts (via remaining_accounts) have distinct, nonzero commitments
+    /// Returns false rather than erroring if any context is plaintext
+    pub fn verify_distinct_commitments<'info>(
+        ctx: Context<'_, '_, 'info, 'info, VerifyDistinctCommitments<'info>>,
+    ) -> Result<bool> {
+        require!(
+            ctx.remaining_accounts.len() <= MAX_BATCH_SIZE,
+            PrismError::BatchTooLarge
+        );
+        
+        let mut seen: Vec<[u8; 32]> = Vec::with_capacity(ctx.remaining_accounts.len());
+        for account_info in ctx.remaining_accounts.iter() {
+            let context: Account<ContextIdentity> = Account::try_from(account_info)?;
+            require!(context.version == CONTEXT_IDENTITY_VERSION, PrismError::UnsupportedVersion);
+            
+            match context.encryption_commitment {
+                Some(commitment) if commitment != [0u8; 32] && !seen.contains(&commitment) => {
+                    seen.push(commitment);
+                }
+                _ => return Ok(false),
+            }
+        }
+        
+        Ok(true)
+    }
+
+    /// Prove a context belongs to one of a set of candidate roots without revealing which
+    /// Plaintext contexts match on root key, encrypted ones on the root identity hash
+    pub fn prove_ring_membership(
+        ctx: Context<ProveRingMembership>,
+        candidate_roots: Vec<Pubkey>,
+    ) -> Result<bool> {
+        require!(
+            !candidate_roots.is_empty() && candidate_roots.len() <= MAX_RING_SIZE,
+            PrismError::InvalidRingSize
+        );
+        
+        let context = &ctx.accounts.context_identity;
+        let is_member = match context.root_identity_hash {
+            Some(root_hash) => candidate_roots
+                .iter()
+                .any(|candidate| root_hash_matches(candidate, &root_hash)),
+            None => candidate_roots.contains(&context.root_identity),
+        };
+        
+        // Commit to the whole ring rather than the matching candidate
+        let ring_bytes: Vec<u8> = candidate_roots
+            .iter()
+            .flat_map(|candidate| candidate.to_bytes())
+            .collect();
+        
+        emit!(RingMembershipProved {
+            context_identity: context.key(),
+            ring_hash: hash(&ring_bytes).to_bytes(),
+            ring_size: candidate_roots.len() as u8,
+            is_member,
+            event_tag: context.event_tag,
+            timestamp: Clock::get()?.unix_timestamp,
+        });
+        
+        Ok(is_member)
+    }
+
+    /// Revoke and close a never-used context in one call, refunding rent immediately
+    /// Contexts with spending history must go through the normal revoke path, and every
+    /// sub-context must be revoked first so none is left without a parent
+    /// With `archive_on_close`, a compact ContextArchive PDA is left behind as a forensic record,
+    /// seeded like the context itself so an encrypted context's archive doesn't name its root.
+    /// Its seeds include the context's created_at, so a root re-created after close_root_identity
+    /// can archive again at indexes its previous incarnation used.
+    pub fn revoke_and_close(
+        ctx: Context<RevokeAndClose>,
+        archive_on_close: bool,
+    ) -> Result<()> {
+        require_owner_quorum!(ctx);
+        let root = &mut ctx.accounts.root_identity;
+        let context = &mut ctx.accounts.context_identity;
+        let now = Clock::get()?.unix_timestamp;
+        
+        require!(!context.revoked, PrismError::ContextAlreadyRevoked);
+        require!(context.total_spent == 0, PrismError::RequiresGracePeriod);
+        require!(context.live_children == 0, PrismError::LiveSubContextsRemain);
+        require!(
+            archive_on_close == ctx.accounts.context_archive.is_some(),
+            PrismError::ArchiveAccountMismatch
+        );
+        
+        let burn_proof = burn_proof_hash(&context.key(), context.total_spent, now);
+        context.revoked = true;
+        context.revoked_at = now;
+        context.burn_proof = Some(burn_proof);
+        root.revoked_count = root.revoked_count.saturating_add(1);
+        
+        if let Some(archive) = ctx.accounts.context_archive.as_mut() {
+            archive.context_identity = context.key();
+            archive.context_type = context.context_type;
+            archive.total_spent = context.total_spent;
+            archive.created_at = context.created_at;
+            archive.closed_at = now;
+            archive.context_index = context.context_index;
+            archive.bump = ctx.bumps.context_archive.unwrap();
+        }
+        
+        emit!(ContextRevoked {
+            root_identity: context.root_identity, // May be zero for encrypted contexts
+            context_identity: context.key(),
+            context_type: context.context_type,
+            total_spent: context.total_spent,
+            context_count: root.context_count,
+            revoked_count: root.revoked_count,
+            event_tag: context.event_tag,
+            burn_proof,
+            timestamp: now,
+        });
+        
+        // Anchor's `close = user` moves the lamports once the handler returns.
+        // root.context_count is left untouched since it seeds future context PDAs
+        root.open_contexts = root.open_contexts.saturating_sub(1);
+        emit!(ContextClosed {
+            context_identity: context.key(),
+            rent_reclaimed: context.to_account_info().lamports(),
+            event_tag: context.event_tag,
+            timestamp: now,
+        });
+        
+        Ok(())
+    }
+
+    /// Bind a context to a signature by the root owner over `challenge`
+    /// The signature is checked via an Ed25519 program instruction placed just before this one
+    pub fn bind_signature_commitment(
+        ctx: Context<BindSignatureCommitment>,
+        challenge: Vec<u8>,
+    ) -> Result<()> {
+        require_owner_quorum!(ctx);
+        let (signer, message) = load_ed25519_signature(&ctx.accounts.instructions)?;
+        
+        require!(
+            signer == ctx.accounts.root_identity.owner && message == challenge,
+            PrismError::SignatureMismatch
+        );
+        
+        let context = &mut ctx.accounts.context_identity;
+        let binding_commitment = signature_binding_commitment(&context.key(), &message);
+        context.signature_binding = Some(binding_commitment);
+        
+        emit_context!(context, SignatureBound {
+            context_identity: context.key(),
+            binding_commitment,
+            event_tag: context.event_tag,
+            timestamp: Clock::get()?.unix_timestamp,
+        });
+        
+        Ok(())
+    }
+
+    /// Check a presented owner signature (via a preceding Ed25519 instruction) matches the binding
+    pub fn verify_signature_binding(ctx: Context<VerifySignatureBinding>) -> Result<bool> {
+        let (signer, message) = load_ed25519_signature(&ctx.accounts.instructions)?;
+        let context = &ctx.accounts.context_identity;
+        
+        if signer != ctx.accounts.root_identity.owner {
+            return Ok(false);
+        }
+        
+        Ok(context.signature_binding == Some(signature_binding_commitment(&context.key(), &message)))
+    }
+
+    /// Report the program id, version and account layouts so SDKs can confirm compatibility
+    /// before sending txs
+    pub fn get_program_info(ctx: Context<GetProgramInfo>) -> Result<ProgramInfo> {
+        Ok(ProgramInfo {
+            program_id: *ctx.program_id,
+            version: PROGRAM_VERSION,
+            root_identity_version: ROOT_IDENTITY_VERSION,
+            context_identity_version: CONTEXT_IDENTITY_VERSION,
+        })
+    }
+
+    /// Check a client-supplied, ordered list of event hashes against the context's event digest
+    /// Each hash is sha256 of the raw event log data (discriminator + payload); any missing
+    /// or reordered event produces a different digest
+    pub fn compute_event_digest(
+        ctx: Context<ComputeEventDigest>,
+        event_hashes: Vec<[u8; 32]>,
+    ) -> Result<bool> {
+        let digest = event_hashes
+            .iter()
+            .fold([0u8; 32], |digest, event_hash| chain_event_hash(&digest, event_hash));
+        
+        Ok(digest == ctx.accounts.context_identity.event_digest)
+    }
+
+    /// Report every time-based constraint currently active on a context and its root
+    /// Inactive or lapsed timelocks are returned as Timelock::INACTIVE
+    pub fn get_active_timelocks(ctx: Context<GetActiveTimelocks>) -> Result<ActiveTimelocks> {
+        let context = &ctx.accounts.context_identity;
+        let root = &ctx.accounts.root_identity;
+        let now = Clock::get()?.unix_timestamp;
+        
+        let one_time_override = if context.one_time_override > 0 {
+            Timelock::until(context.override_expires_at, now)
+        } else {
+            Timelock::INACTIVE
+        };
+        let auth_code = if context.spend_auth_code_hash.is_some() {
+            Timelock::until(context.auth_code_expires_at, now)
+        } else {
+            Timelock::INACTIVE
+        };
+        
+        let expiry = match context.expires_at {
+            Some(expires_at) => Timelock::until(expires_at, now),
+            None => Timelock::INACTIVE,
+        };
+        
+        let reactivation_cooldown = if context.revoked {
+            Timelock::until(context.revoked_at.saturating_add(root.reactivation_cooldown), now)
+        } else {
+            Timelock::INACTIVE
+        };
+        let privacy_downgrade = if root.pending_privacy_level.is_some() {
+            Timelock::until(root.privacy_change_eta, now)
+        } else {
+            Timelock::INACTIVE
+        };
+        let recovery = if root.recovery_new_owner.is_some() {
+            Timelock::until(root.recovery_initiated_at.saturating_add(RECOVERY_TIMELOCK), now)
+        } else {
+            Timelock::INACTIVE
+        };
+        
+        Ok(ActiveTimelocks {
+            expiry,
+            one_time_override,
+            auth_code,
+            reactivation_cooldown,
+            privacy_downgrade,
+            recovery,
+        })
+    }
+
+    /// Read the forensic archive left behind by a closed context
+    pub fn get_archive(ctx: Context<GetArchive>) -> Result<ContextArchive> {
+        Ok((*ctx.accounts.context_archive).clone())
+    }
+
+    /// Self-diagnostic: check a context's fields form a legal combination
+    /// Returns a bitmask of VIOLATION_* flags; 0 means the state is consistent
+    pub fn validate_state_consistency(ctx: Context<ValidateStateConsistency>) -> Result<u32> {
+        Ok(ctx.accounts.context_identity.state_violations())
+    }
+
+    /// Require every spend to be a multiple of `spend_multiple` (lot-size discipline)
+    /// Zero disables the check
+    pub fn set_spend_multiple(
+        ctx: Context<SetSpendMultiple>,
+        spend_multiple: u64,
+    ) -> Result<()> {
+        require_owner_quorum!(ctx);
+        let context = &mut ctx.accounts.context_identity;
+        
+        require!(!context.revoked, PrismError::ContextRevoked);
+        require!(!context.config_sealed, PrismError::ContextSealed);
+        require!(
+            spend_multiple <= context.max_per_transaction,
+            PrismError::InvalidSpendMultiple
+        );
+        
+        context.spend_multiple = spend_multiple;
+        
+        emit_context!(context, SpendMultipleSet {
+            context_identity: context.key(),
+            spend_multiple,
+            event_tag: context.event_tag,
+            timestamp: Clock::get()?.unix_timestamp,
+        });
+        
+        Ok(())
+    }
+
+    /// Fresh liveness proof: succeeds only if the context is currently usable
+    /// Binds the context, current slot and a caller nonce in an ActiveAttestation event
+    pub fn attest_active(ctx: Context<AttestActive>, nonce: u64) -> Result<()> {
+        let context = &ctx.accounts.context_identity;
+        let clock = Clock::get()?;
+        
+        require!(!ctx.accounts.root_identity.frozen, PrismError::RootFrozen);
+        require!(!context.revoked, PrismError::ContextRevoked);
+        require!(!context.is_expired(clock.unix_timestamp), PrismError::ContextExpired);
+        
+        emit!(ActiveAttestation {
+            context_identity: context.key(),
+            slot: clock.slot,
+            nonce,
+            event_tag: context.event_tag,
+            timestamp: clock.unix_timestamp,
+        });
+        
+        Ok(())
+    }
+
+    /// Permissionlessly close a revoked and expired context, returning its rent to the root owner
+    /// Only revoked contexts qualify, so the root's revoked_count already covers the closed
+    /// account and third parties can't skew its active count. root.context_count is never
+    /// decremented since it seeds the PDAs of future contexts. Parents with live
+    /// sub-contexts stay open until those are revoked.
+    pub fn close_expired_context(ctx: Context<CloseExpiredContext>) -> Result<()> {
+        let context = &ctx.accounts.context_identity;
+        let now = Clock::get()?.unix_timestamp;
+        
+        require!(context.revoked, PrismError::ContextNotRevoked);
+        require!(context.is_expired(now), PrismError::ContextNotExpired);
+        require!(context.live_children == 0, PrismError::LiveSubContextsRemain);
+        
+        let root = &mut ctx.accounts.root_identity;
+        root.open_contexts = root.open_contexts.saturating_sub(1);
+        
+        // Anchor's `close = rent_destination` moves the lamports once the handler returns
+        emit!(ContextClosed {
+            context_identity: context.key(),
+            rent_reclaimed: context.to_account_info().lamports(),
+            event_tag: context.event_tag,
+            timestamp: now,
+        });
+        
+        Ok(())
+    }
+
+    /// Close a revoked context or sub-context of this root and reclaim its rent (owner only)
+    /// Unlike close_expired_context this doesn't wait for expiry, so every revoked
+    /// account can be closed ahead of close_root_identity. Parents with live
+    /// sub-contexts stay open until those are revoked.
+    pub fn close_revoked_context(ctx: Context<CloseRevokedContext>) -> Result<()> {
+        require_owner_quorum!(ctx);
+        let context = &ctx.accounts.context_identity;
+        
+        require!(context.revoked, PrismError::ContextNotRevoked);
+        require!(context.live_children == 0, PrismError::LiveSubContextsRemain);
+        
+        let root = &mut ctx.accounts.root_identity;
+        root.open_contexts = root.open_contexts.saturating_sub(1);
+        
+        // Anchor's `close = user` moves the lamports once the handler returns
+        emit!(ContextClosed {
+            context_identity: context.key(),
+            rent_reclaimed: context.to_account_info().lamports(),
+            event_tag: context.event_tag,
+            timestamp: Clock::get()?.unix_timestamp,
+        });
+        
+        Ok(())
+    }
+
+    /// Set the hash of the root's emergency contacts
+    /// Emergency actions echo it so off-chain responders can route alerts without
+    /// the contacts themselves being readable on-chain
+    pub fn set_emergency_contact(
+        ctx: Context<SetEmergencyContact>,
+        emergency_contact_hash: [u8; 32],
+    ) -> Result<()> {
+        require_owner_quorum!(ctx);
+        let root = &mut ctx.accounts.root_identity;
+        root.emergency_contact_hash = emergency_contact_hash;
+        
+        emit!(EmergencyContactSet {
+            root_identity: root.key(),
+            emergency_contact_hash,
+            timestamp: Clock::get()?.unix_timestamp,
+        });
+        
+        Ok(())
+    }
+
+    /// Compute the privacy actually provided by a root, given its contexts (via remaining_accounts)
+    /// Starts from the declared level and is weakened by the worst active context:
+    /// plaintext contexts cap it at PLAINTEXT_CONTEXT_PRIVACY_CAP, Public contexts at
+    /// PUBLIC_CONTEXT_PRIVACY_CAP. Revoked and expired contexts are ignored.
+    pub fn compute_effective_privacy<'info>(
+        ctx: Context<'_, '_, 'info, 'info, ComputeEffectivePrivacy<'info>>,
+    ) -> Result<u8> {
+        require!(
+            ctx.remaining_accounts.len() <= MAX_BATCH_SIZE,
+            PrismError::BatchTooLarge
+        );
+        
+        let root_key = ctx.accounts.root_identity.key();
+        let now = Clock::get()?.unix_timestamp;
+        let mut effective = ctx.accounts.root_identity.privacy_level;
+        
+        for account_info in ctx.remaining_accounts.iter() {
+            let context: Account<ContextIdentity> = Account::try_from(account_info)?;
+            require!(context.version == CONTEXT_IDENTITY_VERSION, PrismError::UnsupportedVersion);
+            require!(
+                context_belongs_to_root(&context, &root_key),
+                PrismError::ContextMismatch
+            );
+            
+            if context.revoked || context.is_expired(now) {
+                continue;
+            }
+            if context.context_type == 5 {
+                effective = effective.max(PUBLIC_CONTEXT_PRIVACY_CAP);
+            }
+            if context.root_identity_hash.is_none() {
+                effective = effective.max(PLAINTEXT_CONTEXT_PRIVACY_CAP);
+            }
+        }
+        
+        Ok(effective)
+    }
+
+    /// Limit total spending to `max_per_window` per rolling `window_duration` seconds
+    /// A duration of zero disables the window limit. Restarts the current window.
+    pub fn set_window_limit(
+        ctx: Context<SetWindowLimit>,
+        window_duration: i64,
+        max_per_window: u64,
+    ) -> Result<()> {
+        require_owner_quorum!(ctx);
+        let context = &mut ctx.accounts.context_identity;
+        let now = Clock::get()?.unix_timestamp;
+        
+        require!(!context.revoked, PrismError::ContextRevoked);
+        require!(!context.config_sealed, PrismError::ContextSealed);
+        require!(window_duration >= 0, PrismError::InvalidWindowDuration);
+        
+        context.window_duration = window_duration;
+        context.max_per_window = max_per_window;
+        context.window_start = now;
+        context.window_spent = 0;
+        
+        emit_context!(context, WindowLimitSet {
+            context_identity: context.key(),
+            window_duration,
+            max_per_window,
+            event_tag: context.event_tag,
+            timestamp: now,
+        });
+        
+        Ok(())
+    }
+
+    /// Set how long a revoked context must stay revoked before it can be reactivated
+    pub fn set_reactivation_cooldown(
+        ctx: Context<SetReactivationCooldown>,
+        reactivation_cooldown: i64,
+    ) -> Result<()> {
+        require_owner_quorum!(ctx);
+        require!(reactivation_cooldown >= 0, PrismError::InvalidCooldown);
+        
+        let root = &mut ctx.accounts.root_identity;
+        root.reactivation_cooldown = reactivation_cooldown;
+        
+        emit!(ReactivationCooldownSet {
+            root_identity: root.key(),
+            reactivation_cooldown,
+            timestamp: Clock::get()?.unix_timestamp,
+        });
+        
+        Ok(())
+    }
+
+    /// Undo a revocation, keeping the context's index and history
+    /// Only allowed once the root's reactivation cooldown has elapsed since revocation.
+    /// Closed contexts no longer deserialize as ContextIdentity, so they can't be reactivated.
+    pub fn reactivate_context(ctx: Context<ReactivateContext>) -> Result<()> {
+        require_owner_quorum!(ctx);
+        let root = &mut ctx.accounts.root_identity;
+        let context = &mut ctx.accounts.context_identity;
+        let now = Clock::get()?.unix_timestamp;
+        
+        require!(context.revoked, PrismError::ContextNotRevoked);
+        require!(!context.is_expired(now), PrismError::ContextExpired);
+        require!(
+            now.saturating_sub(context.revoked_at) >= root.reactivation_cooldown,
+            PrismError::ReactivationCooldownActive
+        );
+        
+        context.revoked = false;
+        context.revoked_at = 0;
+        context.burn_proof = None;
+        root.revoked_count = root.revoked_count.saturating_sub(1);
+        
+        emit_context!(context, ContextReactivated {
+            context_identity: context.key(),
+            context_count: root.context_count,
+            revoked_count: root.revoked_count,
+            event_tag: context.event_tag,
+            timestamp: now,
+        });
+        
+        Ok(())
+    }
+
+    /// Propose a new wallet as owner of the root identity (step one of two)
+    /// Nothing changes until the proposed wallet accepts, so a mistyped key can't lock
+    /// the owner out. The root PDA is seeded by `creator` and never moves.
+    pub fn propose_owner(
+        ctx: Context<ProposeOwner>,
+        new_owner: Pubkey,
+    ) -> Result<()> {
+        require_owner_quorum!(ctx);
+        let root = &mut ctx.accounts.root_identity;
+        
+        require!(new_owner != root.owner, PrismError::SameOwner);
+        
+        root.pending_owner = Some(new_owner);
+        
+        emit!(OwnershipProposed {
+            root_identity: root.key(),
+            owner: root.owner,
+            pending_owner: new_owner,
+            timestamp: Clock::get()?.unix_timestamp,
+        });
+        
+        Ok(())
+    }
+
+    /// Accept a pending ownership proposal (step two, signed by the proposed owner)
+    pub fn accept_ownership(ctx: Context<AcceptOwnership>) -> Result<()> {
+        let root = &mut ctx.accounts.root_identity;
+        let old_owner = root.owner;
+        
+        root.owner = ctx.accounts.new_owner.key();
+        root.pending_owner = None;
+        root.clear_multisig();
+        
+        emit!(OwnershipAccepted {
+            root_identity: root.key(),
+            old_owner,
+            new_owner: root.owner,
+            timestamp: Clock::get()?.unix_timestamp,
+        });
+        
+        Ok(())
+    }
+
+    /// Withdraw a pending ownership proposal; the current owner keeps control
+    pub fn cancel_ownership_transfer(ctx: Context<CancelOwnershipTransfer>) -> Result<()> {
+        let root = &mut ctx.accounts.root_identity;
+        let pending_owner = root.pending_owner.take().ok_or(PrismError::NoPendingOwner)?;
+        
+        emit!(OwnershipTransferCancelled {
+            root_identity: root.key(),
+            owner: root.owner,
+            pending_owner,
+            timestamp: Clock::get()?.unix_timestamp,
+        });
+        
+        Ok(())
+    }
+
+    /// Cap total lamport spending across every context under this root
+    /// Zero means unlimited. Token-denominated contexts are not counted.
+    pub fn set_global_spend_limit(
+        ctx: Context<SetGlobalSpendLimit>,
+        global_spend_limit: u64,
+    ) -> Result<()> {
+        require_owner_quorum!(ctx);
+        let root = &mut ctx.accounts.root_identity;
+        root.global_spend_limit = global_spend_limit;
+        
+        emit!(GlobalSpendLimitSet {
+            root_identity: root.key(),
+            global_spend_limit,
+            global_spent: root.global_spent,
+            timestamp: Clock::get()?.unix_timestamp,
+        });
+        
+        Ok(())
+    }
+
+    /// Create several plaintext contexts in one instruction
+    /// The context PDAs are passed in order via remaining_accounts and get sequential
+    /// indices starting at the root's current context_count. Each uses default settings
+    /// (no reserved space, tag, expiry, mint or minimum) and emits its own ContextCreated.
+    pub fn create_contexts_batch<'info>(
+        ctx: Context<'_, '_, 'info, 'info, CreateContextsBatch<'info>>,
+        count: u8,
+        specs: Vec<ContextSpec>,
+    ) -> Result<()> {
+        require_owner_quorum!(ctx);
+        let count = count as usize;
+        require!(count > 0 && count <= MAX_BATCH_SIZE, PrismError::BatchTooLarge);
+        // Signers are multisig co-signers, not the new contexts
+        let contexts: Vec<&AccountInfo<'info>> = ctx.remaining_accounts
+            .iter()
+            .filter(|account| !account.is_signer)
+            .collect();
+        require!(
+            specs.len() == count && contexts.len() == count,
+            PrismError::BatchLengthMismatch
+        );
+        
+        let root = &mut ctx.accounts.root_identity;
+        let root_key = root.key();
+        let first_index = root.context_count;
+        require!(
+            first_index as usize + count <= root.max_contexts as usize,
+            PrismError::MaxContextsReached
+        );
+        
+        let clock = Clock::get()?;
+        
+        // Each entry goes through the same checks as create_context, including the
+        // creation cooldown, so a cooldown limits a batch to a single context
+        for (spec, account_info) in specs.iter().zip(contexts.iter().copied()) {
+            let index_bytes = root.context_count.to_le_bytes();
+            let (expected, bump) = Pubkey::find_program_address(
+                &[b"context", root_key.as_ref(), &index_bytes],
+                &crate::ID,
+            );
+            require_keys_eq!(account_info.key(), expected, PrismError::ContextMismatch);
+            
+            create_pda_account(
+                &ctx.accounts.user.to_account_info(),
+                account_info,
+                &ctx.accounts.system_program.to_account_info(),
+                ContextIdentity::SIZE,
+                &[&[b"context", root_key.as_ref(), &index_bytes, &[bump]]],
+            )?;
+            ContextIdentity::default().try_serialize(&mut &mut account_info.try_borrow_mut_data()?[..])?;
+            
+            let mut context: Account<ContextIdentity> = Account::try_from(account_info)?;
+            init_context(
+                root,
+                &mut context,
+                bump,
+                spec.context_type,
+                spec.max_per_transaction,
+                0,
+                None,
+                None,
+                None,
+                None,
+                [0u8; 32],
+                0,
+                None,
+            )?;
+            context.exit(&crate::ID)?;
+        }
+        
+        emit!(ContextBatchCreated {
+            root_identity: root_key,
+            first_index,
+            count: count as u8,
+            created_slot: clock.slot,
+            timestamp: clock.unix_timestamp,
+        });
+        
+        Ok(())
+    }
+
+    /// Emergency burn: revoke a batch of contexts (via remaining_accounts) in one instruction
+    /// Already-revoked contexts are skipped, or fail the whole batch if
+    /// `fail_on_already_revoked` is set
+    pub fn revoke_all_contexts<'info>(
+        ctx: Context<'_, '_, 'info, 'info, RevokeAllContexts<'info>>,
+        fail_on_already_revoked: bool,
+    ) -> Result<()> {
+        require_owner_quorum!(ctx);
+        let root = &mut ctx.accounts.root_identity;
+        let root_key = root.key();
+        let now = Clock::get()?.unix_timestamp;
+        
+        // Signers are multisig co-signers, not contexts
+        let contexts: Vec<&AccountInfo<'info>> = ctx.remaining_accounts
+            .iter()
+            .filter(|account| !account.is_signer)
+            .collect();
+        require!(contexts.len() <= MAX_BATCH_SIZE, PrismError::BatchTooLarge);
+        
+        let mut count: u8 = 0;
+        for account_info in contexts.iter().copied() {
+            let mut context: Account<ContextIdentity> = Account::try_from(account_info)?;
+            require!(context.version == CONTEXT_IDENTITY_VERSION, PrismError::UnsupportedVersion);
+            
+            require!(
+                context_belongs_to_root(&context, &root_key),
+                PrismError::ContextMismatch
+            );
+            if context.revoked {
+                require!(!fail_on_already_revoked, PrismError::ContextAlreadyRevoked);
+                continue;
+            }
+            
+            context.revoked = true;
+            context.revoked_at = now;
+            context.burn_proof = Some(burn_proof_hash(&context.key(), context.total_spent, now));
+            context.exit(&crate::ID)?;
+            count += 1;
+        }
+        
+        root.revoked_count = root.revoked_count.saturating_add(count as u16);
+        
+        emit!(ContextsRevoked {
+            root_identity: root_key,
+            count,
+            context_count: root.context_count,
+            revoked_count: root.revoked_count,
+            timestamp: now,
+        });
+        
+        Ok(())
+    }
+
+    /// Kill switch: halt spending on every context under this root until unfrozen
+    pub fn freeze_root(ctx: Context<FreezeRoot>) -> Result<()> {
+        let root = &mut ctx.accounts.root_identity;
+        let now = Clock::get()?.unix_timestamp;
+        
+        require!(!root.frozen, PrismError::RootFrozen);
+        root.frozen = true;
+        
+        emit!(RootFrozen {
+            root_identity: root.key(),
+            timestamp: now,
+        });
+        emit!(EmergencyAction {
+            root_identity: root.key(),
+            action: EMERGENCY_ACTION_FREEZE,
+            emergency_contact_hash: root.emergency_contact_hash,
+            timestamp: now,
+        });
+        
+        Ok(())
+    }
+
+    /// Lift a freeze once the owner has secured their wallet
+    pub fn unfreeze_root(ctx: Context<FreezeRoot>) -> Result<()> {
+        require_owner_quorum!(ctx);
+        let root = &mut ctx.accounts.root_identity;
+        let now = Clock::get()?.unix_timestamp;
+        
+        require!(root.frozen, PrismError::RootNotFrozen);
+        root.frozen = false;
+        
+        emit!(RootUnfrozen {
+            root_identity: root.key(),
+            timestamp: now,
+        });
+        emit!(EmergencyAction {
+            root_identity: root.key(),
+            action: EMERGENCY_ACTION_UNFREEZE,
+            emergency_contact_hash: root.emergency_contact_hash,
+            timestamp: now,
+        });
+        
+        Ok(())
+    }
+
+    /// Configure up to MAX_GUARDIANS recovery guardians and how many must approve
+    /// Unused slots are Pubkey::default(); a threshold of zero disables recovery.
+    /// Any pending recovery is discarded.
+    pub fn set_guardians(
+        ctx: Context<SetGuardians>,
+        guardians: [Pubkey; MAX_GUARDIANS],
+        recovery_threshold: u8,
+    ) -> Result<()> {
+        require_owner_quorum!(ctx);
+        let root = &mut ctx.accounts.root_identity;
+        
+        let active: Vec<&Pubkey> = guardians.iter().filter(|g| **g != Pubkey::default()).collect();
+        let distinct = active.iter().enumerate().all(|(i, g)| !active[..i].contains(g));
+        require!(
+            distinct && !active.iter().any(|g| root.is_owner(g)) && recovery_threshold as usize <= active.len(),
+            PrismError::InvalidGuardianConfig
+        );
+        
+        root.guardians = guardians;
+        root.recovery_threshold = recovery_threshold;
+        root.recovery_new_owner = None;
+        root.recovery_initiated_at = 0;
+        root.recovery_approvals = 0;
+        
+        emit!(GuardiansSet {
+            root_identity: root.key(),
+            guardians,
+            recovery_threshold,
+            timestamp: Clock::get()?.unix_timestamp,
+        });
+        
+        Ok(())
+    }
+
+    /// Start recovering the root to `new_owner` (signed by a guardian, counts as their approval)
+    /// The owner can cancel until RECOVERY_TIMELOCK has passed and enough guardians approved
+    pub fn initiate_recovery(
+        ctx: Context<GuardianRecovery>,
+        new_owner: Pubkey,
+    ) -> Result<()> {
+        let root = &mut ctx.accounts.root_identity;
+        let now = Clock::get()?.unix_timestamp;
+        
+        require!(root.recovery_threshold > 0, PrismError::RecoveryNotConfigured);
+        require!(root.recovery_new_owner.is_none(), PrismError::RecoveryInProgress);
+        require!(new_owner != root.owner, PrismError::SameOwner);
+        let index = root
+            .guardian_index(&ctx.accounts.guardian.key())
+            .ok_or(PrismError::NotAGuardian)?;
+        
+        root.recovery_new_owner = Some(new_owner);
+        root.recovery_initiated_at = now;
+        root.recovery_approvals = 1 << index;
+        
+        emit!(RecoveryInitiated {
+            root_identity: root.key(),
+            guardian: ctx.accounts.guardian.key(),
+            new_owner,
+            timestamp: now,
+        });
+        emit!(EmergencyAction {
+            root_identity: root.key(),
+            action: EMERGENCY_ACTION_RECOVERY_INITIATED,
+            emergency_contact_hash: root.emergency_contact_hash,
+            timestamp: now,
+        });
+        
+        Ok(())
+    }
+
+    /// Approve the pending recovery (signed by a guardian)
+    pub fn approve_recovery(ctx: Context<GuardianRecovery>) -> Result<()> {
+        let root = &mut ctx.accounts.root_identity;
+        
+        let new_owner = root.recovery_new_owner.ok_or(PrismError::NoRecoveryPending)?;
+        let index = root
+            .guardian_index(&ctx.accounts.guardian.key())
+            .ok_or(PrismError::NotAGuardian)?;
+        
+        root.recovery_approvals |= 1 << index;
+        
+        emit!(RecoveryApproved {
+            root_identity: root.key(),
+            guardian: ctx.accounts.guardian.key(),
+            new_owner,
+            approvals: root.recovery_approvals.count_ones() as u8,
+            timestamp: Clock::get()?.unix_timestamp,
+        });
+        
+        Ok(())
+    }
+
+    /// Complete a recovery once the threshold is met and the timelock has passed
+    /// Permissionless: anyone can execute an approved recovery
+    pub fn execute_recovery(ctx: Context<ExecuteRecovery>) -> Result<()> {
+        let root = &mut ctx.accounts.root_identity;
+        let now = Clock::get()?.unix_timestamp;
+        
+        let new_owner = root.recovery_new_owner.ok_or(PrismError::NoRecoveryPending)?;
+        require!(
+            root.recovery_approvals.count_ones() >= root.recovery_threshold as u32,
+            PrismError::RecoveryThresholdNotMet
+        );
+        require!(
+            now >= root.recovery_initiated_at.saturating_add(RECOVERY_TIMELOCK),
+            PrismError::RecoveryTimelockActive
+        );
+        
+        let old_owner = root.owner;
+        root.owner = new_owner;
+        root.pending_owner = None;
+        root.clear_multisig();
+        root.recovery_new_owner = None;
+        root.recovery_initiated_at = 0;
+        root.recovery_approvals = 0;
+        
+        emit!(OwnershipAccepted {
+            root_identity: root.key(),
+            old_owner,
+            new_owner,
+            timestamp: now,
+        });
+        emit!(EmergencyAction {
+            root_identity: root.key(),
+            action: EMERGENCY_ACTION_RECOVERY_EXECUTED,
+            emergency_contact_hash: root.emergency_contact_hash,
+            timestamp: now,
+        });
+        
+        Ok(())
+    }
+
+    /// Cancel a pending guardian recovery (owner only)
+    pub fn cancel_recovery(ctx: Context<CancelRecovery>) -> Result<()> {
+        let root = &mut ctx.accounts.root_identity;
+        
+        let new_owner = root.recovery_new_owner.take().ok_or(PrismError::NoRecoveryPending)?;
+        root.recovery_initiated_at = 0;
+        root.recovery_approvals = 0;
+        
+        emit!(RecoveryCancelled {
+            root_identity: root.key(),
+            new_owner,
+            timestamp: Clock::get()?.unix_timestamp,
+        });
+        
+        Ok(())
+    }
+
+    /// Summarize a context's usability and remaining allowance in one simulatable call
+    /// Returned as program return data so clients can skip parsing the account
+    pub fn get_context_status(ctx: Context<GetContextStatus>) -> Result<ContextStatus> {
+        let context = &ctx.accounts.context_identity;
+        let now = Clock::get()?.unix_timestamp;
+        
+        Ok(ContextStatus {
+            revoked: context.revoked,
+            expired: context.is_expired(now),
+            total_spent: context.total_spent,
+            max_per_transaction: context.max_per_transaction,
+            remaining_window: context.window_remaining(now),
+            expires_at: context.expires_at,
+        })
+    }
+
+    /// Bring a RootIdentity or ContextIdentity account up to the current layout version
+    /// Accounts from before versioning have no version byte and a different field order,
+    /// so they are recognized by their exact size and decoded from the legacy layout.
+    /// Versioned layouts only ever append fields: the account is grown (payer tops up
+    /// rent), read with a zero-filled tail and backfilled one version step at a time.
+    /// Signed by the root owner; a context also needs its (already migrated) root.
+    /// A root from before v9 recounts its open contexts: pass every context and
+    /// sub-context account it still has in remaining_accounts, since any left out are
+    /// not counted and the root could be closed while they're still open. A context
+    /// from before v15 likewise recounts its live sub-contexts from remaining_accounts.
+    pub fn migrate_account<'info>(ctx: Context<'_, '_, 'info, 'info, MigrateAccount<'info>>) -> Result<()> {
+        let account = &ctx.accounts.account;
+        let owner = ctx.accounts.owner.key();
+        
+        let (discriminator, data) = {
+            let data = account.try_borrow_data()?;
+            require!(data.len() > 8, PrismError::UnsupportedVersion);
+            (data[..8].to_vec(), data.to_vec())
+        };
+        
+        let (from_version, to_version, target_size, migrated) = if discriminator == RootIdentity::DISCRIMINATOR {
+            let (from_version, mut root) = if data.len() == LegacyRootIdentity::SIZE {
+                let legacy = LegacyRootIdentity::deserialize(&mut &data[8..])?;
+                (0, legacy.upgrade())
+            } else {
+                // Zero was never a valid version byte
+                require!(data[8] >= 1, PrismError::UnsupportedVersion);
+                (data[8], read_padded::<RootIdentity>(&data, RootIdentity::SIZE)?)
+            };
+            require!(from_version <= ROOT_IDENTITY_VERSION, PrismError::UnsupportedVersion);
+            require!(
+                from_version < ROOT_IDENTITY_VERSION || data.len() < RootIdentity::SIZE,
+                PrismError::AlreadyMigrated
+            );
+            require!(root.is_owner(&owner), PrismError::Unauthorized);
+            
+            // Per-version backfills for fields whose default isn't zero
+            if from_version < 9 {
+                // context_count also counts contexts closed since, so count what's left
+                root.open_contexts = count_open_contexts(&account.key(), ctx.remaining_accounts)?;
+            }
+            root.version = ROOT_IDENTITY_VERSION;
+            
+            let mut buffer = Vec::with_capacity(RootIdentity::SIZE);
+            root.try_serialize(&mut buffer)?;
+            (from_version, ROOT_IDENTITY_VERSION, RootIdentity::SIZE, buffer)
+        } else if discriminator == ContextIdentity::DISCRIMINATOR {
+            let (from_version, mut context) = read_any_context(&data)?;
+            require!(
+                from_version < CONTEXT_IDENTITY_VERSION || data.len() < ContextIdentity::SIZE,
+                PrismError::AlreadyMigrated
+            );
+            
+            let root = ctx.accounts.root_identity.as_ref().ok_or(PrismError::ContextMismatch)?;
+            require!(root.is_owner(&owner), PrismError::Unauthorized);
+            require!(
+                identity_belongs_to_root(&context, &account.key(), &root.key()),
+                PrismError::ContextMismatch
+            );
+            
+            // Per-version backfills for fields whose default isn't zero
+            if from_version < 13 && context.revoked {
+                context.burn_proof = Some(burn_proof_hash(&account.key(), context.total_spent, context.revoked_at));
+            }
+            if from_version < 15 {
+                context.live_children = count_live_children(&account.key(), ctx.remaining_accounts)?;
+            }
+            context.version = CONTEXT_IDENTITY_VERSION;
+            
+            let mut buffer = Vec::with_capacity(ContextIdentity::SIZE);
+            context.try_serialize(&mut buffer)?;
+            (from_version, CONTEXT_IDENTITY_VERSION, ContextIdentity::SIZE, buffer)
+        } else {
+            return err!(PrismError::UnsupportedVersion);
+        };
+        
+        if account.data_len() < target_size {
+            grow_account(
+                &account.to_account_info(),
+                &ctx.accounts.payer.to_account_info(),
+                &ctx.accounts.system_program.to_account_info(),
+                target_size,
+            )?;
+        }
+        // Bytes past the current layout (a context's reserved space) are left as they are
+        account.try_borrow_mut_data()?[..migrated.len()].copy_from_slice(&migrated);
+        
+        emit!(AccountMigrated {
+            account: account.key(),
+            from_version,
+            to_version,
+            timestamp: Clock::get()?.unix_timestamp,
+        });
+        
+        Ok(())
+    }
+
+    /// Grow a context created under an older, smaller layout to the current SIZE
+    /// The payer covers the rent delta and the new tail is zero-initialized. Takes the
+    /// account unchecked since a short account can't deserialize as ContextIdentity yet;
+    /// the PDA seeds tie it to the signer's root.
+    pub fn resize_context(ctx: Context<ResizeContext>, _context_index: u16) -> Result<()> {
+        require_owner_quorum!(ctx);
+        let context = &ctx.accounts.context_identity;
+        let old_size = context.data_len();
+        let new_size = ContextIdentity::SIZE;
+        
+        require!(
+            &context.try_borrow_data()?[..8] == ContextIdentity::DISCRIMINATOR,
+            PrismError::ContextMismatch
+        );
+        require!(new_size > old_size, PrismError::ResizeWouldShrink);
+        
+        grow_account(
+            &context.to_account_info(),
+            &ctx.accounts.user.to_account_info(),
+            &ctx.accounts.system_program.to_account_info(),
+            new_size,
+        )?;
+        
+        emit!(ContextResized {
+            context_identity: context.key(),
+            old_size: old_size as u32,
+            new_size: new_size as u32,
+            timestamp: Clock::get()?.unix_timestamp,
+        });
+        
+        Ok(())
+    }
+
+    /// Rotate an encrypted context's commitment (e.g. after Arcium re-keying)
+    /// A new root identity hash may be supplied too; it is re-checked against the root
+    pub fn update_encryption_commitment(
+        ctx: Context<UpdateEncryptionCommitment>,
+        new_commitment: [u8; 32],
+        new_root_identity_hash: Option<[u8; 32]>,
+    ) -> Result<()> {
+        require_owner_quorum!(ctx);
+        let root_key = ctx.accounts.root_identity.key();
+        let context = &mut ctx.accounts.context_identity;
+        let now = Clock::get()?.unix_timestamp;
+        
+        require!(!context.revoked, PrismError::ContextRevoked);
+        require!(!context.config_sealed, PrismError::ContextSealed);
+        let old_commitment = context
+            .encryption_commitment
+            .ok_or(PrismError::ContextNotEncrypted)?;
+        
+        if let Some(root_identity_hash) = new_root_identity_hash {
+            require!(
+                hash_root_identity(&root_key) == root_identity_hash,
+                PrismError::InvalidRootHash
+            );
+            context.root_identity_hash = Some(root_identity_hash);
+        }
+        context.encryption_commitment = Some(new_commitment);
+        context.commitment_updated_at = now;
+        
+        emit_context!(context, CommitmentRotated {
+            context_identity: context.key(),
+            old_commitment,
+            new_commitment,
+            event_tag: context.event_tag,
+            timestamp: now,
+        });
+        
+        Ok(())
+    }
+
+    /// Set the default per-transaction limit for each context type (indexed by ContextType)
+    /// Used when a context is created with max_per_transaction == USE_TYPE_DEFAULT
+    pub fn set_type_defaults(
+        ctx: Context<SetTypeDefaults>,
+        type_defaults: [u64; 6],
+    ) -> Result<()> {
+        require_owner_quorum!(ctx);
+        let root = &mut ctx.accounts.root_identity;
+        root.type_defaults = type_defaults;
+        
+        emit!(TypeDefaultsSet {
+            root_identity: root.key(),
+            type_defaults,
+            timestamp: Clock::get()?.unix_timestamp,
+        });
+        
+        Ok(())
+    }
+
+    /// Create the program-wide config (once, right after deploy)
+    /// Only the program's upgrade authority may do this, so whoever wins the race to the
+    /// config PDA can't take the pause switch; it becomes the first admin.
+    pub fn initialize_config(ctx: Context<InitializeConfig>) -> Result<()> {
+        let config = &mut ctx.accounts.config;
+        config.admin = ctx.accounts.admin.key();
+        config.paused = false;
+        config.bump = ctx.bumps.config;
+        config.verbose_events = false;
+        
+        emit!(ConfigInitialized {
+            admin: config.admin,
+            timestamp: Clock::get()?.unix_timestamp,
+        });
+        
+        Ok(())
+    }
+
+    /// Circuit breaker: halt every mutating instruction except the protective ones
+    /// (revocation, root freeze and cancelling pending ownership changes)
+    pub fn pause(ctx: Context<SetPaused>) -> Result<()> {
+        let config = &mut ctx.accounts.config;
+        require!(!config.paused, PrismError::ProgramPaused);
+        config.paused = true;
+        
+        emit!(ProgramPaused {
+            admin: config.admin,
+            timestamp: Clock::get()?.unix_timestamp,
+        });
+        
+        Ok(())
+    }
+
+    /// Lift a program-wide pause
+    pub fn unpause(ctx: Context<SetPaused>) -> Result<()> {
+        let config = &mut ctx.accounts.config;
+        require!(config.paused, PrismError::ProgramNotPaused);
+        config.paused = false;
+        
+        emit!(ProgramUnpaused {
+            admin: config.admin,
+            timestamp: Clock::get()?.unix_timestamp,
+        });
+        
+        Ok(())
+    }
+
+    /// Hand the config admin role to another key
+    /// Both the current and the new admin sign, so the role can't be sent to a key
+    /// nobody controls.
+    pub fn set_config_admin(ctx: Context<SetConfigAdmin>) -> Result<()> {
+        let config = &mut ctx.accounts.config;
+        let previous_admin = config.admin;
+        config.admin = ctx.accounts.new_admin.key();
+        
+        emit!(ConfigAdminChanged {
+            previous_admin,
+            new_admin: config.admin,
+            timestamp: Clock::get()?.unix_timestamp,
+        });
+        
+        Ok(())
+    }
+
+    /// Turn optional events (currently SpendHook) on or off program-wide
+    pub fn set_verbose_events(ctx: Context<SetVerboseEvents>, enabled: bool) -> Result<()> {
+        let config = &mut ctx.accounts.config;
+        config.verbose_events = enabled;
+        
+        emit!(VerboseEventsSet {
+            admin: config.admin,
+            enabled,
+            timestamp: Clock::get()?.unix_timestamp,
+        });
+        
+        Ok(())
+    }
+
+    /// Spawn a short-lived child of a context (e.g. one per trade)
+    /// The child inherits the parent's root binding, mint, program restriction and
+    /// expiry; its PDA is derived from the parent key and a local child index.
+    /// Its per-transaction limits are clamped to the parent's, and sealed parents can't
+    /// spawn children since that would loosen a frozen configuration.
+    /// Sub-contexts are live accounts of the root, so the root's max_contexts (counted
+    /// over open_contexts here) and creation cooldown apply to them too.
+    pub fn create_sub_context(
+        ctx: Context<CreateSubContext>,
+        context_type: u8,
+        max_per_transaction: u64,
+    ) -> Result<()> {
+        require_owner_quorum!(ctx);
+        require!(context_type <= 5, PrismError::InvalidContextType);
+        
+        let clock = Clock::get()?;
+        let now = clock.unix_timestamp;
+        let root = &mut ctx.accounts.root_identity;
+        let parent = &mut ctx.accounts.parent_context;
+        let child = &mut ctx.accounts.sub_context;
+        
+        require!(root.open_contexts < root.max_contexts as u32, PrismError::MaxContextsReached);
+        require!(!root.creation_cooldown_active(now), PrismError::CreationCooldownActive);
+        require!(!parent.revoked, PrismError::ContextRevoked);
+        require!(!parent.config_sealed, PrismError::ContextSealed);
+        require!(!parent.is_expired(now), PrismError::ContextExpired);
+        require!(
+            context_type_allowed(
+                root.privacy_level,
+                context_type,
+                parent.root_identity_hash.is_some(),
+            ),
+            PrismError::ContextTypeNotAllowedAtPrivacyLevel
+        );
+        
+        child.version = CONTEXT_IDENTITY_VERSION;
+        child.root_identity = parent.root_identity;
+        child.root_identity_hash = parent.root_identity_hash;
+        child.encryption_commitment = parent.encryption_commitment;
+        child.encryption_epoch = parent.encryption_epoch;
+        child.computation_id = parent.computation_id;
+        child.context_type = context_type;
+        child.created_at = now;
+        child.created_slot = clock.slot;
+        child.expires_at = parent.expires_at;
+        child.mint = parent.mint;
+        child.allowed_program = parent.allowed_program;
+        child.verifier_pubkey = parent.verifier_pubkey;
+        child.max_per_transaction = max_per_transaction.min(parent.max_per_transaction);
+        // An inherited minimum above the clamped maximum would leave the child unable to spend
+        child.min_per_transaction = parent.min_per_transaction.min(child.max_per_transaction);
+        child.event_tag = parent.event_tag;
+        child.context_index = parent.child_count;
+        child.bump = ctx.bumps.sub_context;
+        child.parent_context = Some(parent.key());
+        
+        parent.child_count = parent.child_count
+            .checked_add(1)
+            .ok_or(PrismError::MaxContextsReached)?;
+        parent.live_children = parent.live_children.checked_add(1).unwrap();
+        root.open_contexts = root.open_contexts.checked_add(1).unwrap();
+        root.last_context_created_at = now;
+        
+        emit_context!(child, SubContextCreated {
+            parent_context: parent.key(),
+            context_identity: child.key(),
+            child_index: child.context_index,
+            context_type,
+            max_per_transaction: child.max_per_transaction,
+            event_tag: child.event_tag,
+            created_slot: child.created_slot,
+            timestamp: now,
+        });
+        
+        Ok(())
+    }
+
+    /// Record spending through a sub-context
+    /// The child's own limits apply, and the amount is also charged to the parent under
+    /// all of the parent's limits, so a child can never spend past its parent
+    #[allow(clippy::too_many_arguments)]
+    pub fn record_sub_spending(
+        ctx: Context<RecordSubSpending>,
+        amount: u64,
+        auth_code: Option<Vec<u8>>,
+        expected_amount: Option<u64>,
+        max_slippage_bps: Option<u16>,
+        hook_data: Option<[u8; 32]>,
+        target_program: Pubkey,
+        nonce: u64,
+    ) -> Result<()> {
+        require_owner_quorum!(ctx);
+        let clock = Clock::get()?;
+        let parent = &mut ctx.accounts.parent_context;
+        check_context_spend(parent, amount, &target_program, &clock)?;
+        roll_up_spending(parent, amount, &clock)?;
+        
+        apply_spending(
+            &mut ctx.accounts.root_identity,
+            &mut ctx.accounts.sub_context,
+            amount,
+            auth_code,
+            expected_amount,
+            max_slippage_bps,
+            hook_data.filter(|_| ctx.accounts.config.verbose_events),
+            target_program,
+            nonce,
+        )
+    }
+
+    /// Revoke one sub-context on its own, leaving the parent live
+    /// Also reaches children a parent's revoke_context cascade left out, so they can be
+    /// closed and stop holding the parent and the root open
+    pub fn revoke_sub_context(ctx: Context<RevokeSubContext>) -> Result<()> {
+        require_owner_quorum!(ctx);
+        let parent = &mut ctx.accounts.parent_context;
+        let child = &mut ctx.accounts.sub_context;
+        
+        require!(!child.revoked, PrismError::ContextAlreadyRevoked);
+        
+        let now = Clock::get()?.unix_timestamp;
+        child.revoked = true;
+        child.revoked_at = now;
+        child.burn_proof = Some(burn_proof_hash(&child.key(), child.total_spent, now));
+        parent.live_children = parent.live_children.saturating_sub(1);
+        
+        emit_context!(parent, SubContextsRevoked {
+            parent_context: parent.key(),
+            count: 1,
+            event_tag: parent.event_tag,
+            timestamp: now,
+        });
+        
+        Ok(())
+    }
+
+    /// Set (or clear) the key that signs commitment proofs for verify_commitment
+    pub fn set_verifier_key(
+        ctx: Context<SetVerifierKey>,
+        verifier_pubkey: Option<Pubkey>,
+    ) -> Result<()> {
+        require_owner_quorum!(ctx);
+        let context = &mut ctx.accounts.context_identity;
+        
+        require!(!context.revoked, PrismError::ContextRevoked);
+        require!(!context.config_sealed, PrismError::ContextSealed);
+        
+        context.verifier_pubkey = verifier_pubkey;
+        
+        emit_context!(context, VerifierKeySet {
+            context_identity: context.key(),
+            verifier_pubkey,
+            event_tag: context.event_tag,
+            timestamp: Clock::get()?.unix_timestamp,
+        });
+        
+        Ok(())
+    }
+
+    /// Give back budget consumed by a trade that was cancelled or only partially filled
+    /// Root owner (or multisig quorum) only; unlike record_spending, delegates can't refund.
+    /// Sealed contexts and frozen roots can't be refunded, and a refund may not exceed
+    /// what the current window and epoch still count, so it can't reset older budgets.
+    /// Each refund also takes its spend back off spend_count.
+    pub fn refund_spending(
+        ctx: Context<RefundSpending>,
+        amount: u64,
+    ) -> Result<()> {
+        require_owner_quorum!(ctx);
+        let clock = Clock::get()?;
+        let root = &mut ctx.accounts.root_identity;
+        let context = &mut ctx.accounts.context_identity;
+        
+        require!(!root.frozen, PrismError::RootFrozen);
+        require!(!context.revoked, PrismError::ContextRevoked);
+        require!(!context.config_sealed, PrismError::ContextSealed);
+        require!(amount > 0, PrismError::ZeroRefund);
+        
+        if context.window_duration > 0 {
+            require!(
+                amount <= context.window_spent_at(clock.unix_timestamp),
+                PrismError::RefundExceedsCurrentSpend
+            );
+            context.window_spent -= amount;
+        }
+        if context.max_per_epoch > 0 {
+            require!(
+                amount <= context.epoch_spent_at(clock.epoch),
+                PrismError::RefundExceedsCurrentSpend
+            );
+            context.epoch_spent -= amount;
+        }
+        context.total_spent = context.total_spent
+            .checked_sub(amount)
+            .ok_or(PrismError::RefundUnderflow)?;
+        context.spend_count = context.spend_count.saturating_sub(1);
+        // Roots migrated from before global_spent existed may count less than their contexts
+        if context.mint.is_none() {
+            root.global_spent = root.global_spent.saturating_sub(amount);
+        }
+        
+        emit_context!(context, SpendingRefunded {
+            context_identity: context.key(),
+            amount,
+            total_spent: context.total_spent,
+            spend_count: context.spend_count,
+            mint: context.mint,
+            event_tag: context.event_tag,
+            timestamp: clock.unix_timestamp,
+        });
+        
+        Ok(())
+    }
+
+    /// Rename a context (root owner only); the label is client-facing and not secret
+    pub fn set_context_label(
+        ctx: Context<SetContextLabel>,
+        label: [u8; 32],
+    ) -> Result<()> {
+        require_owner_quorum!(ctx);
+        require!(is_valid_label(&label), PrismError::InvalidLabel);
+        
+        let context = &mut ctx.accounts.context_identity;
+        require!(!context.revoked, PrismError::ContextRevoked);
+        require!(!context.config_sealed, PrismError::ContextSealed);
+        context.label = label;
+        
+        emit_context!(context, ContextLabelSet {
+            context_identity: context.key(),
+            label,
+            event_tag: context.event_tag,
+            timestamp: Clock::get()?.unix_timestamp,
+        });
+        
+        Ok(())
+    }
+
+    /// Close the root identity and reclaim its rent (owner only)
+    /// Every context and sub-context account must be closed first (revoke_and_close,
+    /// close_expired_context or close_revoked_context). The root PDA is seeded on the
+    /// creator wallet, so the same wallet can call create_root_identity again afterwards;
+    /// the new root starts at context_count 0, which is safe because no account is left
+    /// at the old context addresses to collide with or be reactivated.
+    pub fn close_root_identity(ctx: Context<CloseRootIdentity>) -> Result<()> {
+        require_owner_quorum!(ctx);
+        let root = &ctx.accounts.root_identity;
+        
+        require!(root.open_contexts == 0, PrismError::ActiveContextsRemain);
+        
+        emit!(RootIdentityClosed {
+            root_identity: root.key(),
+            owner: root.owner,
+            context_count: root.context_count,
+            timestamp: Clock::get()?.unix_timestamp,
+        });
+        
+        Ok(())
+    }
+
+    /// verify_commitment for encrypted contexts without the root account or an owner
+    /// signature, so the transaction doesn't link the context back to the wallet.
+    /// The context is identified by its root_identity_hash instead of its PDA seeds.
+    pub fn verify_commitment_encrypted(
+        ctx: Context<VerifyCommitmentEncrypted>,
+        _root_identity_hash: [u8; 32],
+        commitment: [u8; 32],
+        binding_key: Pubkey,
+    ) -> Result<CommitmentVerification> {
+        let (signer, message) = load_ed25519_signature(&ctx.accounts.instructions)?;
+        let is_valid = commitment_proof_valid(
+            &ctx.accounts.context_identity,
+            &commitment,
+            &binding_key,
+            &signer,
+            &message,
+        );
+        
+        Ok(CommitmentVerification { is_valid, binding_key })
+    }
+
+    /// check_spending_limit for encrypted contexts without the root account or an owner signature
+    /// Root-wide state (freeze, global cap) can't be read without revealing the root,
+    /// so only the context's own limits are checked; record_spending still enforces both.
+    pub fn check_spending_limit_encrypted(
+        ctx: Context<CheckSpendingLimitEncrypted>,
+        _root_identity_hash: [u8; 32],
+        amount: u64,
+        target_program: Pubkey,
+    ) -> Result<()> {
+        check_context_spend(
+            &ctx.accounts.context_identity,
+            amount,
+            &target_program,
+            &Clock::get()?,
+        )
+    }
+
+    /// Limit total spending to `max_per_epoch` per Solana epoch (0 disables it)
+    /// Independent of the rolling window; integrators can use either or both.
+    pub fn set_epoch_limit(
+        ctx: Context<SetEpochLimit>,
+        max_per_epoch: u64,
+    ) -> Result<()> {
+        require_owner_quorum!(ctx);
+        let context = &mut ctx.accounts.context_identity;
+        let clock = Clock::get()?;
+        
+        require!(!context.revoked, PrismError::ContextRevoked);
+        require!(!context.config_sealed, PrismError::ContextSealed);
+        
+        context.max_per_epoch = max_per_epoch;
+        context.last_epoch = clock.epoch;
+        context.epoch_spent = 0;
+        
+        emit_context!(context, EpochLimitSet {
+            context_identity: context.key(),
+            max_per_epoch,
+            epoch: clock.epoch,
+            event_tag: context.event_tag,
+            timestamp: clock.unix_timestamp,
+        });
+        
+        Ok(())
+    }
+
+    /// Publish a new Merkle root over this root's context keys (owner only)
+    /// Leaves are hash(CONTEXT_LEAF_DOMAIN || context key); interior nodes hash
+    /// CONTEXT_NODE_DOMAIN with the two children in ascending byte order, so proofs
+    /// don't need left/right flags. The tree is maintained off-chain.
+    pub fn update_contexts_merkle_root(
+        ctx: Context<UpdateContextsMerkleRoot>,
+        merkle_root: [u8; 32],
+    ) -> Result<()> {
+        require_owner_quorum!(ctx);
+        let root = &mut ctx.accounts.root_identity;
+        let old_root = root.contexts_merkle_root;
+        root.contexts_merkle_root = merkle_root;
+        
+        emit!(ContextsMerkleRootUpdated {
+            root_identity: root.key(),
+            old_root,
+            new_root: merkle_root,
+            timestamp: Clock::get()?.unix_timestamp,
+        });
+        
+        Ok(())
+    }
+
+    /// Check a Merkle path proves `context` is in the root's published context set
+    /// Lets light clients prove membership without revealing the other contexts
+    pub fn verify_context_inclusion(
+        ctx: Context<VerifyContextInclusion>,
+        context: Pubkey,
+        proof: Vec<[u8; 32]>,
+    ) -> Result<bool> {
+        require!(proof.len() <= MAX_MERKLE_DEPTH, PrismError::MerkleProofTooLong);
+        
+        let merkle_root = ctx.accounts.root_identity.contexts_merkle_root;
+        if merkle_root == [0u8; 32] {
+            return Ok(false);
+        }
+        
+        Ok(merkle_root_from_proof(&context, &proof) == merkle_root)
+    }
+
+    /// Let a session key spend through this context (None removes it)
+    /// The delegate can call record_spending and check_spending_limit, but can't
+    /// revoke the context, refund spends or change its limits. Sealed contexts keep
+    /// whatever delegate they were sealed with.
+    pub fn set_context_delegate(
+        ctx: Context<SetContextDelegate>,
+        delegate: Option<Pubkey>,
+    ) -> Result<()> {
+        require_owner_quorum!(ctx);
+        let context = &mut ctx.accounts.context_identity;
+        
+        require!(!context.revoked, PrismError::ContextRevoked);
+        require!(!context.config_sealed, PrismError::ContextSealed);
+        
+        let old_delegate = context.delegate;
+        context.delegate = delegate;
+        
+        emit_context!(context, ContextDelegateSet {
+            context_identity: context.key(),
+            old_delegate,
+            new_delegate: delegate,
+            event_tag: context.event_tag,
+            timestamp: Clock::get()?.unix_timestamp,
+        });
+        
+        Ok(())
+    }
+
+    /// Propose lowering the root's privacy level; it can be finalized once the
+    /// root's privacy timelock has passed. Replaces any pending proposal.
+    pub fn propose_privacy_change(
+        ctx: Context<UpdatePrivacyLevel>,
+        new_privacy_level: u8,
+    ) -> Result<()> {
+        require_owner_quorum!(ctx);
+        require!(new_privacy_level <= 4, PrismError::InvalidPrivacyLevel);
+        
+        let root = &mut ctx.accounts.root_identity;
+        require!(new_privacy_level > root.privacy_level, PrismError::NotAPrivacyDowngrade);
+        
+        let now = Clock::get()?.unix_timestamp;
+        let eta = now.saturating_add(root.privacy_timelock());
+        root.pending_privacy_level = Some(new_privacy_level);
+        root.privacy_change_eta = eta;
+        
+        emit!(PrivacyChangeProposed {
+            root_identity: root.key(),
+            current_level: root.privacy_level,
+            pending_level: new_privacy_level,
+            eta,
+            timestamp: now,
+        });
+        
+        Ok(())
+    }
+
+    /// Apply a proposed privacy downgrade once its ETA has passed
+    pub fn finalize_privacy_change(ctx: Context<UpdatePrivacyLevel>) -> Result<()> {
+        require_owner_quorum!(ctx);
+        let root = &mut ctx.accounts.root_identity;
+        let now = Clock::get()?.unix_timestamp;
+        
+        let new_level = root.pending_privacy_level.ok_or(PrismError::NoPendingPrivacyChange)?;
+        require!(now >= root.privacy_change_eta, PrismError::PrivacyDowngradeTimelocked);
+        
+        let old_level = root.privacy_level;
+        root.privacy_level = new_level;
+        root.pending_privacy_level = None;
+        root.privacy_change_eta = 0;
+        
+        emit!(PrivacyLevelUpdated {
+            root_identity: root.key(),
+            old_level,
+            new_level,
+            timestamp: now,
+        });
+        
+        Ok(())
+    }
+
+    /// Drop a pending privacy downgrade (owner only, works while paused)
+    pub fn cancel_privacy_change(ctx: Context<CancelOwnershipTransfer>) -> Result<()> {
+        let root = &mut ctx.accounts.root_identity;
+        let pending_level = root.pending_privacy_level.take().ok_or(PrismError::NoPendingPrivacyChange)?;
+        root.privacy_change_eta = 0;
+        
+        emit!(PrivacyChangeCancelled {
+            root_identity: root.key(),
+            pending_level,
+            timestamp: Clock::get()?.unix_timestamp,
+        });
+        
+        Ok(())
+    }
+
+    /// Set how long privacy downgrades wait before they can be finalized
+    /// Must be at least MIN_PRIVACY_DOWNGRADE_TIMELOCK; applies to future proposals only
+    pub fn set_privacy_timelock(
+        ctx: Context<UpdatePrivacyLevel>,
+        privacy_downgrade_timelock: i64,
+    ) -> Result<()> {
+        require_owner_quorum!(ctx);
+        require!(
+            privacy_downgrade_timelock >= MIN_PRIVACY_DOWNGRADE_TIMELOCK,
+            PrismError::InvalidPrivacyTimelock
+        );
+        
+        let root = &mut ctx.accounts.root_identity;
+        root.privacy_downgrade_timelock = privacy_downgrade_timelock;
+        
+        emit!(PrivacyTimelockSet {
+            root_identity: root.key(),
+            privacy_downgrade_timelock,
+            timestamp: Clock::get()?.unix_timestamp,
+        });
+        
+        Ok(())
+    }
+
+    /// Current decayed linkability score of a context, as return data
+    /// Each spend adds amount / LINKABILITY_AMOUNT_UNIT; the score then loses
+    /// LINKABILITY_DECAY_PER_SECOND per second, so old activity stops counting.
+    pub fn get_linkability_score(ctx: Context<GetContextStatus>) -> Result<u64> {
+        Ok(ctx.accounts.context_identity.linkability_at(Clock::get()?.unix_timestamp))
+    }
+
+    /// Check `burn_proof` is the proof recorded when this context was revoked
+    /// Recomputes hash(context || total_spent || revoked_at) from the account, so a
+    /// proof from the ContextRevoked event can gate e.g. withdrawals via CPI without
+    /// trusting an indexer. Fails if the context isn't currently revoked.
+    pub fn verify_burn_proof(ctx: Context<GetContextStatus>, burn_proof: [u8; 32]) -> Result<bool> {
+        let context = &ctx.accounts.context_identity;
+        
+        require!(context.revoked, PrismError::ContextNotRevoked);
+        let stored = context.burn_proof.ok_or(PrismError::ContextNotRevoked)?;
+        let expected = burn_proof_hash(&context.key(), context.total_spent, context.revoked_at);
+        
+        Ok(stored == expected && burn_proof == expected)
+    }
+
+    /// Limit how many spends a context may ever record (0 = unlimited)
+    /// e.g. a Temporary context good for exactly N trades regardless of size
+    pub fn set_max_spend_count(
+        ctx: Context<SetMaxSpendCount>,
+        max_spend_count: u32,
+    ) -> Result<()> {
+        require_owner_quorum!(ctx);
+        let context = &mut ctx.accounts.context_identity;
+        
+        require!(!context.revoked, PrismError::ContextRevoked);
+        require!(!context.config_sealed, PrismError::ContextSealed);
+        
+        context.max_spend_count = max_spend_count;
+        
+        emit_context!(context, MaxSpendCountSet {
+            context_identity: context.key(),
+            max_spend_count,
+            spend_count: context.spend_count,
+            event_tag: context.event_tag,
+            timestamp: Clock::get()?.unix_timestamp,
+        });
+        
+        Ok(())
+    }
+
+    /// Require at least `creation_cooldown` seconds between context creations (0 disables it)
+    /// Spreads new identities out so a burst can't be clustered as one user's.
+    /// Batches of more than one context are rejected while a cooldown is set.
+    pub fn set_creation_cooldown(
+        ctx: Context<SetCreationCooldown>,
+        creation_cooldown: i64,
+    ) -> Result<()> {
+        require_owner_quorum!(ctx);
+        require!(creation_cooldown >= 0, PrismError::InvalidCooldown);
+        
+        let root = &mut ctx.accounts.root_identity;
+        root.creation_cooldown = creation_cooldown;
+        
+        emit!(CreationCooldownSet {
+            root_identity: root.key(),
+            creation_cooldown,
+            timestamp: Clock::get()?.unix_timestamp,
+        });
+        
+        Ok(())
+    }
+
+    /// Change a context's per-transaction limit in place (owner only)
+    /// The limit is per transaction, so it is independent of total_spent and may go
+    /// below it; it must still cover min_per_transaction and spend_multiple.
+    /// USE_TYPE_DEFAULT takes the root's current default for the context type.
+    pub fn update_context_limit(
+        ctx: Context<UpdateContextLimit>,
+        max_per_transaction: u64,
+    ) -> Result<()> {
+        require_owner_quorum!(ctx);
+        let root = &ctx.accounts.root_identity;
+        let context = &mut ctx.accounts.context_identity;
+        
+        require!(!context.revoked, PrismError::ContextRevoked);
+        require!(!context.config_sealed, PrismError::ContextSealed);
+        
+        let new_limit = root.resolve_limit(context.context_type, max_per_transaction);
+        require!(
+            new_limit >= context.min_per_transaction && new_limit >= context.spend_multiple,
+            PrismError::InvalidTransactionLimit
+        );
+        
+        let old_limit = context.max_per_transaction;
+        context.max_per_transaction = new_limit;
+        
+        emit_context!(context, ContextLimitUpdated {
+            context_identity: context.key(),
+            old_limit,
+            new_limit,
+            event_tag: context.event_tag,
+            timestamp: Clock::get()?.unix_timestamp,
+        });
+        
+        Ok(())
+    }
+}
+
+// ============================================================================
+// ACCOUNT CONTEXTS
+// ============================================================================
+
+#[derive(Accounts)]
+pub struct CreateRootIdentity<'info> {
+    #[account(mut)]
+    pub user: Signer<'info>,
+    
+    #[account(
+        init,
+        payer = user,
+        space = RootIdentity::SIZE,
+        seeds = [b"root", user.key().as_ref()],
+        bump
+    )]
+    pub root_identity: Account<'info, RootIdentity>,
+    
+    pub system_program: Program<'info, System>,
+    
+    #[account(
+        seeds = [b"config"],
+        bump = config.bump,
+        constraint = !config.paused @ PrismError::ProgramPaused
+    )]
+    pub config: Account<'info, ProgramConfig>,
+}
+
+#[derive(Accounts)]
+#[instruction(context_type: u8, max_per_transaction: u64, reserved_space: u16)]
+pub struct CreateContext<'info> {
+    #[account(mut)]
+    pub user: Signer<'info>,
+    
+    #[account(
+        mut,
+        seeds = [b"root", root_identity.creator.as_ref()],
+        bump = root_identity.bump,
+        constraint = root_identity.is_owner(&user.key()) @ PrismError::Unauthorized,
+        constraint = root_identity.version == ROOT_IDENTITY_VERSION @ PrismError::UnsupportedVersion
+    )]
+    pub root_identity: Account<'info, RootIdentity>,
+    
+    #[account(
+        init,
+        payer = user,
+        space = ContextIdentity::SIZE + reserved_space as usize,
+        seeds = [
+            b"context",
+            root_identity.key().as_ref(),
+            &root_identity.context_count.to_le_bytes()
+        ],
+        bump
+    )]
+    pub context_identity: Account<'info, ContextIdentity>,
+    
+    pub system_program: Program<'info, System>,
+    
+    #[account(
+        seeds = [b"config"],
+        bump = config.bump,
+        constraint = !config.paused @ PrismError::ProgramPaused
+    )]
+    pub config: Account<'info, ProgramConfig>,
+}
+
+#[derive(Accounts)]
+#[instruction(
+    context_type: u8,
+    max_per_transaction: u64,
+    reserved_space: u16,
+    root_identity_hash: [u8; 32],
+    encryption_commitment: [u8; 32]
+)]
+pub struct CreateEncryptedContext<'info> {
+    #[account(mut)]
+    pub user: Signer<'info>,
+    
+    #[account(
+        mut,
+        seeds = [b"root", root_identity.creator.as_ref()],
+        bump = root_identity.bump,
+        constraint = root_identity.is_owner(&user.key()) @ PrismError::Unauthorized,
+        constraint = root_identity.version == ROOT_IDENTITY_VERSION @ PrismError::UnsupportedVersion
+    )]
+    pub root_identity: Account<'info, RootIdentity>,
+    
+    #[account(
+        init,
+        payer = user,
+        space = ContextIdentity::SIZE + reserved_space as usize,
+        seeds = [
+            ENCRYPTED_CONTEXT_SEED,
+            encryption_commitment.as_ref(),
+            &root_identity.context_count.to_le_bytes()
+        ],
+        bump
+    )]
+    pub context_identity: Account<'info, ContextIdentity>,
+    
+    pub system_program: Program<'info, System>,
+    
+    #[account(
+        seeds = [b"config"],
+        bump = config.bump,
+        constraint = !config.paused @ PrismError::ProgramPaused
+    )]
+    pub config: Account<'info, ProgramConfig>,
+}
+
+#[derive(Accounts)]
+pub struct RevokeContext<'info> {
+    #[account(mut)]
+    pub user: Signer<'info>,
+    
+    #[account(
+        mut,
+        seeds = [b"root", root_identity.creator.as_ref()],
+        bump = root_identity.bump,
+        constraint = root_identity.is_owner(&user.key()) @ PrismError::Unauthorized,
+        constraint = root_identity.version == ROOT_IDENTITY_VERSION @ PrismError::UnsupportedVersion
+    )]
+    pub root_identity: Account<'info, RootIdentity>,
+    
+    #[account(
+        mut,
+        seeds = [
+            context_identity.seed_prefix(&root_identity.key()),
+            &context_identity.seed_key(&root_identity.key()),
+            &context_identity.context_index.to_le_bytes()
+        ],
+        bump = context_identity.bump,
+        constraint = context_identity.version == CONTEXT_IDENTITY_VERSION @ PrismError::UnsupportedVersion
+    )]
+    pub context_identity: Account<'info, ContextIdentity>,
+}
+
+#[derive(Accounts)]
+pub struct VerifyCommitment<'info> {
+    #[account(
+        seeds = [
+            context_identity.seed_prefix(&root_identity.key()),
+            // For encrypted contexts, derive from root_identity account instead
+            // This requires passing root_identity as a separate account
+            &context_identity.seed_key(&root_identity.key()),
+            &context_identity.context_index.to_le_bytes()
+        ],
+        bump = context_identity.bump,
+        constraint = context_identity.version == CONTEXT_IDENTITY_VERSION @ PrismError::UnsupportedVersion
+    )]
+    pub context_identity: Account<'info, ContextIdentity>,
+    
+    // Need root_identity account to derive PDA for encrypted contexts
+    #[account(
+        seeds = [b"root", root_identity.creator.as_ref()],
+        bump = root_identity.bump,
+        constraint = root_identity.version == ROOT_IDENTITY_VERSION @ PrismError::UnsupportedVersion
+    )]
+    pub root_identity: Account<'info, RootIdentity>,
+    
+    pub user: Signer<'info>,
+
+    /// CHECK: Instructions sysvar, validated by address
+    #[account(address = anchor_lang::solana_program::sysvar::instructions::ID)]
+    pub instructions: UncheckedAccount<'info>,
+}
+
+#[derive(Accounts)]
+pub struct CheckSpendingLimit<'info> {
+    #[account(
+        seeds = [
+            context_identity.seed_prefix(&root_identity.key()),
+            &context_identity.seed_key(&root_identity.key()),
+            &context_identity.context_index.to_le_bytes()
+        ],
+        bump = context_identity.bump,
+        constraint = root_identity.is_owner(&user.key()) || context_identity.delegate == Some(user.key()) @ PrismError::NotDelegate,
+        constraint = context_identity.version == CONTEXT_IDENTITY_VERSION @ PrismError::UnsupportedVersion
+    )]
+    pub context_identity: Account<'info, ContextIdentity>,
+    
+    // Need root_identity account to derive PDA for encrypted contexts
+    #[account(
+        seeds = [b"root", root_identity.creator.as_ref()],
+        bump = root_identity.bump,
+        constraint = root_identity.version == ROOT_IDENTITY_VERSION @ PrismError::UnsupportedVersion
+    )]
+    pub root_identity: Account<'info, RootIdentity>,
+    
+    pub user: Signer<'info>,
+}
+
+#[derive(Accounts)]
+pub struct RecordSpending<'info> {
+    #[account(mut)]
+    pub user: Signer<'info>,
+    
+    #[account(
+        mut,
+        seeds = [b"root", root_identity.creator.as_ref()],
+        bump = root_identity.bump,
+        constraint = root_identity.version == ROOT_IDENTITY_VERSION @ PrismError::UnsupportedVersion
+    )]
+    pub root_identity: Account<'info, RootIdentity>,
+
+    /// Token-denominated contexts must go through record_spending_token
+    #[account(
+        mut,
+        seeds = [
+            context_identity.seed_prefix(&root_identity.key()),
+            &context_identity.seed_key(&root_identity.key()),
+            &context_identity.context_index.to_le_bytes()
+        ],
+        bump = context_identity.bump,
+        constraint = root_identity.is_owner(&user.key()) || context_identity.delegate == Some(user.key()) @ PrismError::NotDelegate,
+        constraint = context_identity.mint.is_none() @ PrismError::MintMismatch,
+        constraint = context_identity.version == CONTEXT_IDENTITY_VERSION @ PrismError::UnsupportedVersion
+    )]
+    pub context_identity: Account<'info, ContextIdentity>,
+    
+    #[account(
+        seeds = [b"config"],
+        bump = config.bump,
+        constraint = !config.paused @ PrismError::ProgramPaused
+    )]
+    pub config: Account<'info, ProgramConfig>,
+}
+
+#[derive(Accounts)]
+pub struct RecordSpendingToken<'info> {
+    #[account(mut)]
+    pub user: Signer<'info>,
+    
+    #[account(
+        mut,
+        seeds = [b"root", root_identity.creator.as_ref()],
+        bump = root_identity.bump,
+        constraint = root_identity.version == ROOT_IDENTITY_VERSION @ PrismError::UnsupportedVersion
+    )]
+    pub root_identity: Account<'info, RootIdentity>,
+    
+    #[account(
+        mut,
+        seeds = [
+            context_identity.seed_prefix(&root_identity.key()),
+            &context_identity.seed_key(&root_identity.key()),
+            &context_identity.context_index.to_le_bytes()
+        ],
+        bump = context_identity.bump,
+        constraint = root_identity.is_owner(&user.key()) || context_identity.delegate == Some(user.key()) @ PrismError::NotDelegate,
+        constraint = context_identity.version == CONTEXT_IDENTITY_VERSION @ PrismError::UnsupportedVersion
+    )]
+    pub context_identity: Account<'info, ContextIdentity>,
+
+    /// CHECK: an initialized SPL Token mint, checked by is_token_mint. Not Account<Mint>:
+    /// anchor-spl's IDL support for that type requires its token_2022 feature
+    #[account(
+        constraint = is_token_mint(&mint) @ PrismError::MintMismatch,
+        constraint = context_identity.mint == Some(mint.key()) @ PrismError::MintMismatch
+    )]
+    pub mint: UncheckedAccount<'info>,
+    
+    #[account(
+        seeds = [b"config"],
+        bump = config.bump,
+        constraint = !config.paused @ PrismError::ProgramPaused
+    )]
+    pub config: Account<'info, ProgramConfig>,
+}
+
+#[derive(Accounts)]
+pub struct UpdatePrivacyLevel<'info> {
+    #[account(mut)]
+    pub user: Signer<'info>,
+    
+    #[account(
+        mut,
+        seeds = [b"root", root_identity.creator.as_ref()],
+        bump = root_identity.bump,
+        constraint = root_identity.is_owner(&user.key()) @ PrismError::Unauthorized,
+        constraint = root_identity.version == ROOT_IDENTITY_VERSION @ PrismError::UnsupportedVersion
+    )]
+    pub root_identity: Account<'info, RootIdentity>,
+    
+    #[account(
+        seeds = [b"config"],
+        bump = config.bump,
+        constraint = !config.paused @ PrismError::ProgramPaused
+    )]
+    pub config: Account<'info, ProgramConfig>,
+}
+
+#[derive(Accounts)]
+pub struct SealContext<'info> {
+    #[account(mut)]
+    pub user: Signer<'info>,
+    
+    #[account(
+        seeds = [b"root", root_identity.creator.as_ref()],
+        bump = root_identity.bump,
+        constraint = root_identity.is_owner(&user.key()) @ PrismError::Unauthorized,
+        constraint = root_identity.version == ROOT_IDENTITY_VERSION @ PrismError::UnsupportedVersion
+    )]
+    pub root_identity: Account<'info, RootIdentity>,
+    
+    #[account(
+        mut,
+        seeds = [
+            context_identity.seed_prefix(&root_identity.key()),
+            &context_identity.seed_key(&root_identity.key()),
+            &context_identity.context_index.to_le_bytes()
+        ],
+        bump = context_identity.bump,
+        constraint = context_identity.version == CONTEXT_IDENTITY_VERSION @ PrismError::UnsupportedVersion
+    )]
+    pub context_identity: Account<'info, ContextIdentity>,
+    
+    #[account(
+        seeds = [b"config"],
+        bump = config.bump,
+        constraint = !config.paused @ PrismError::ProgramPaused
+    )]
+    pub config: Account<'info, ProgramConfig>,
+}
+
+#[derive(Accounts)]
+pub struct RotateEncryptionEpoch<'info> {
+    #[account(mut)]
+    pub user: Signer<'info>,
+    
+    #[account(
+        mut,
+        seeds = [b"root", root_identity.creator.as_ref()],
+        bump = root_identity.bump,
+        constraint = root_identity.is_owner(&user.key()) @ PrismError::Unauthorized,
+        constraint = root_identity.version == ROOT_IDENTITY_VERSION @ PrismError::UnsupportedVersion
+    )]
+    pub root_identity: Account<'info, RootIdentity>,
+    
+    #[account(
+        seeds = [b"config"],
+        bump = config.bump,
+        constraint = !config.paused @ PrismError::ProgramPaused
+    )]
+    pub config: Account<'info, ProgramConfig>,
+}
+
+#[derive(Accounts)]
+pub struct VerifySameEpoch<'info> {
+    #[account(constraint = context_a.version == CONTEXT_IDENTITY_VERSION @ PrismError::UnsupportedVersion)]
+    pub context_a: Account<'info, ContextIdentity>,
+    
+    #[account(constraint = context_b.version == CONTEXT_IDENTITY_VERSION @ PrismError::UnsupportedVersion)]
+    pub context_b: Account<'info, ContextIdentity>,
+}
+
+#[derive(Accounts)]
+pub struct SetPrivacyPolicy<'info> {
+    #[account(mut)]
+    pub user: Signer<'info>,
+    
+    #[account(
+        mut,
+        seeds = [b"root", root_identity.creator.as_ref()],
+        bump = root_identity.bump,
+        constraint = root_identity.is_owner(&user.key()) @ PrismError::Unauthorized,
+        constraint = root_identity.version == ROOT_IDENTITY_VERSION @ PrismError::UnsupportedVersion
+    )]
+    pub root_identity: Account<'info, RootIdentity>,
+    
+    #[account(
+        seeds = [b"config"],
+        bump = config.bump,
+        constraint = !config.paused @ PrismError::ProgramPaused
+    )]
+    pub config: Account<'info, ProgramConfig>,
+}
+
+#[derive(Accounts)]
+pub struct VerifyPrivacyPolicy<'info> {
+    #[account(constraint = root_identity.version == ROOT_IDENTITY_VERSION @ PrismError::UnsupportedVersion)]
+    pub root_identity: Account<'info, RootIdentity>,
+}
+
+#[derive(Accounts)]
+pub struct AuthorizeOverride<'info> {
+    #[account(mut)]
+    pub user: Signer<'info>,
+    
+    #[account(
+        seeds = [b"root", root_identity.creator.as_ref()],
+        bump = root_identity.bump,
+        constraint = root_identity.is_owner(&user.key()) @ PrismError::Unauthorized,
+        constraint = root_identity.version == ROOT_IDENTITY_VERSION @ PrismError::UnsupportedVersion
+    )]
+    pub root_identity: Account<'info, RootIdentity>,
+    
+    #[account(
+        mut,
+        seeds = [
+            context_identity.seed_prefix(&root_identity.key()),
+            &context_identity.seed_key(&root_identity.key()),
+            &context_identity.context_index.to_le_bytes()
+        ],
+        bump = context_identity.bump,
+        constraint = context_identity.version == CONTEXT_IDENTITY_VERSION @ PrismError::UnsupportedVersion
+    )]
+    pub context_identity: Account<'info, ContextIdentity>,
+    
+    #[account(
+        seeds = [b"config"],
+        bump = config.bump,
+        constraint = !config.paused @ PrismError::ProgramPaused
+    )]
+    pub config: Account<'info, ProgramConfig>,
+}
+
+#[derive(Accounts)]
+pub struct AttestSpending<'info> {
+    pub user: Signer<'info>,
+    
+    #[account(
+        seeds = [b"root", root_identity.creator.as_ref()],
+        bump = root_identity.bump,
+        constraint = root_identity.is_owner(&user.key()) @ PrismError::Unauthorized,
+        constraint = root_identity.version == ROOT_IDENTITY_VERSION @ PrismError::UnsupportedVersion
+    )]
+    pub root_identity: Account<'info, RootIdentity>,
+    
+    #[account(
+        seeds = [
+            context_identity.seed_prefix(&root_identity.key()),
+            &context_identity.seed_key(&root_identity.key()),
+            &context_identity.context_index.to_le_bytes()
+        ],
+        bump = context_identity.bump,
+        constraint = context_identity.version == CONTEXT_IDENTITY_VERSION @ PrismError::UnsupportedVersion
+    )]
+    pub context_identity: Account<'info, ContextIdentity>,
+}
+
+#[derive(Accounts)]
+pub struct MigrateContextsToEpoch<'info> {
+    #[account(mut)]
+    pub user: Signer<'info>,
+    
+    #[account(
+        seeds = [b"root", root_identity.creator.as_ref()],
+        bump = root_identity.bump,
+        constraint = root_identity.is_owner(&user.key()) @ PrismError::Unauthorized,
+        constraint = root_identity.version == ROOT_IDENTITY_VERSION @ PrismError::UnsupportedVersion
+    )]
+    pub root_identity: Account<'info, RootIdentity>,
+    
+    #[account(
+        seeds = [b"config"],
+        bump = config.bump,
+        constraint = !config.paused @ PrismError::ProgramPaused
+    )]
+    pub config: Account<'info, ProgramConfig>,
+}
+
+#[derive(Accounts)]
+pub struct VerifyComputationBinding<'info> {
+    #[account(constraint = context_identity.version == CONTEXT_IDENTITY_VERSION @ PrismError::UnsupportedVersion)]
+    pub context_identity: Account<'info, ContextIdentity>,
+}
+
+#[derive(Accounts)]
+pub struct SetSpendAuthCode<'info> {
+    #[account(mut)]
+    pub user: Signer<'info>,
+    
+    #[account(
+        seeds = [b"root", root_identity.creator.as_ref()],
+        bump = root_identity.bump,
+        constraint = root_identity.is_owner(&user.key()) @ PrismError::Unauthorized,
+        constraint = root_identity.version == ROOT_IDENTITY_VERSION @ PrismError::UnsupportedVersion
+    )]
+    pub root_identity: Account<'info, RootIdentity>,
+    
+    #[account(
+        mut,
+        seeds = [
+            context_identity.seed_prefix(&root_identity.key()),
+            &context_identity.seed_key(&root_identity.key()),
+            &context_identity.context_index.to_le_bytes()
+        ],
+        bump = context_identity.bump,
+        constraint = context_identity.version == CONTEXT_IDENTITY_VERSION @ PrismError::UnsupportedVersion
+    )]
+    pub context_identity: Account<'info, ContextIdentity>,
+    
+    #[account(
+        seeds = [b"config"],
+        bump = config.bump,
+        constraint = !config.paused @ PrismError::ProgramPaused
+    )]
+    pub config: Account<'info, ProgramConfig>,
+}
+
+#[derive(Accounts)]
+pub struct GetCounterpartyPaymentAddress<'info> {
+    #[account(constraint = context_identity.version == CONTEXT_IDENTITY_VERSION @ PrismError::UnsupportedVersion)]
+    pub context_identity: Account<'info, ContextIdentity>,
+}
+
+#[derive(Accounts)]
+#[instruction(counterparty: Pubkey)]
+pub struct SweepCounterpartyPayment<'info> {
+    #[account(mut)]
+    pub user: Signer<'info>,
+    
+    #[account(
+        seeds = [b"root", root_identity.creator.as_ref()],
+        bump = root_identity.bump,
+        constraint = root_identity.is_owner(&user.key()) @ PrismError::Unauthorized,
+        constraint = root_identity.version == ROOT_IDENTITY_VERSION @ PrismError::UnsupportedVersion
+    )]
+    pub root_identity: Account<'info, RootIdentity>,
+    
+    #[account(
+        seeds = [
+            context_identity.seed_prefix(&root_identity.key()),
+            &context_identity.seed_key(&root_identity.key()),
+            &context_identity.context_index.to_le_bytes()
+        ],
+        bump = context_identity.bump,
+        constraint = context_identity.version == CONTEXT_IDENTITY_VERSION @ PrismError::UnsupportedVersion
+    )]
+    pub context_identity: Account<'info, ContextIdentity>,
+    
+    #[account(
+        mut,
+        seeds = [b"pay", context_identity.key().as_ref(), counterparty.as_ref()],
+        bump
+    )]
+    pub payment_address: SystemAccount<'info>,
+    
+    pub system_program: Program<'info, System>,
+    
+    #[account(
+        seeds = [b"config"],
+        bump = config.bump,
+        constraint = !config.paused @ PrismError::ProgramPaused
+    )]
+    pub config: Account<'info, ProgramConfig>,
+}
+
+#[derive(Accounts)]
+pub struct VerifyDistinctCommitments<'info> {
+    // Placeholder so the generated CPI client (which always takes a lifetime) compiles
+    pub prism_program: Program<'info, crate::program::Prism>,
+}
+
+#[derive(Accounts)]
+pub struct ProveRingMembership<'info> {
+    #[account(constraint = context_identity.version == CONTEXT_IDENTITY_VERSION @ PrismError::UnsupportedVersion)]
+    pub context_identity: Account<'info, ContextIdentity>,
+}
+
+#[derive(Accounts)]
+pub struct RevokeAndClose<'info> {
+    #[account(mut)]
+    pub user: Signer<'info>,
+    
+    #[account(
+        mut,
+        seeds = [b"root", root_identity.creator.as_ref()],
+        bump = root_identity.bump,
+        constraint = root_identity.is_owner(&user.key()) @ PrismError::Unauthorized,
+        constraint = root_identity.version == ROOT_IDENTITY_VERSION @ PrismError::UnsupportedVersion
+    )]
+    pub root_identity: Account<'info, RootIdentity>,
+    
+    #[account(
+        mut,
+        close = user,
+        seeds = [
+            context_identity.seed_prefix(&root_identity.key()),
+            &context_identity.seed_key(&root_identity.key()),
+            &context_identity.context_index.to_le_bytes()
+        ],
+        bump = context_identity.bump,
+        constraint = context_identity.version == CONTEXT_IDENTITY_VERSION @ PrismError::UnsupportedVersion
+    )]
+    pub context_identity: Account<'info, ContextIdentity>,
+    
+    #[account(
+        init,
+        payer = user,
+        space = ContextArchive::SIZE,
+        seeds = [
+            context_identity.archive_prefix(&root_identity.key()),
+            &context_identity.seed_key(&root_identity.key()),
+            &context_identity.context_index.to_le_bytes(),
+            &context_identity.created_at.to_le_bytes()
+        ],
+        bump
+    )]
+    pub context_archive: Option<Account<'info, ContextArchive>>,
+    
+    pub system_program: Program<'info, System>,
+    
+    #[account(
+        seeds = [b"config"],
+        bump = config.bump,
+        constraint = !config.paused @ PrismError::ProgramPaused
+    )]
+    pub config: Account<'info, ProgramConfig>,
+}
+
+#[derive(Accounts)]
+pub struct BindSignatureCommitment<'info> {
+    #[account(mut)]
+    pub user: Signer<'info>,
+    
+    #[account(
+        seeds = [b"root", root_identity.creator.as_ref()],
+        bump = root_identity.bump,
+        constraint = root_identity.is_owner(&user.key()) @ PrismError::Unauthorized,
+        constraint = root_identity.version == ROOT_IDENTITY_VERSION @ PrismError::UnsupportedVersion
+    )]
+    pub root_identity: Account<'info, RootIdentity>,
+    
+    #[account(
+        mut,
+        seeds = [
+            context_identity.seed_prefix(&root_identity.key()),
+            &context_identity.seed_key(&root_identity.key()),
+            &context_identity.context_index.to_le_bytes()
+        ],
+        bump = context_identity.bump,
+        constraint = context_identity.version == CONTEXT_IDENTITY_VERSION @ PrismError::UnsupportedVersion
+    )]
+    pub context_identity: Account<'info, ContextIdentity>,
+
+    /// CHECK: Instructions sysvar, validated by address
+    #[account(address = anchor_lang::solana_program::sysvar::instructions::ID)]
+    pub instructions: UncheckedAccount<'info>,
+    
+    #[account(
+        seeds = [b"config"],
+        bump = config.bump,
+        constraint = !config.paused @ PrismError::ProgramPaused
+    )]
+    pub config: Account<'info, ProgramConfig>,
+}
+
+#[derive(Accounts)]
+pub struct VerifySignatureBinding<'info> {
+    #[account(constraint = root_identity.version == ROOT_IDENTITY_VERSION @ PrismError::UnsupportedVersion)]
+    pub root_identity: Account<'info, RootIdentity>,
+    
+    #[account(
+        seeds = [
+            context_identity.seed_prefix(&root_identity.key()),
+            &context_identity.seed_key(&root_identity.key()),
+            &context_identity.context_index.to_le_bytes()
+        ],
+        bump = context_identity.bump,
+        constraint = context_identity.version == CONTEXT_IDENTITY_VERSION @ PrismError::UnsupportedVersion
+    )]
+    pub context_identity: Account<'info, ContextIdentity>,
+
+    /// CHECK: Instructions sysvar, validated by address
+    #[account(address = anchor_lang::solana_program::sysvar::instructions::ID)]
+    pub instructions: UncheckedAccount<'info>,
+}
+
+#[derive(Accounts)]
+pub struct GetProgramInfo<'info> {
+    // Placeholder so the generated CPI client (which always takes a lifetime) compiles
+    pub prism_program: Program<'info, crate::program::Prism>,
+}
+
+#[derive(Accounts)]
+pub struct ComputeEventDigest<'info> {
+    #[account(constraint = context_identity.version == CONTEXT_IDENTITY_VERSION @ PrismError::UnsupportedVersion)]
+    pub context_identity: Account<'info, ContextIdentity>,
+}
+
+#[derive(Accounts)]
+pub struct GetActiveTimelocks<'info> {
+    #[account(constraint = context_identity.version == CONTEXT_IDENTITY_VERSION @ PrismError::UnsupportedVersion)]
+    pub context_identity: Account<'info, ContextIdentity>,
+    
+    #[account(
+        seeds = [b"root", root_identity.creator.as_ref()],
+        bump = root_identity.bump,
+        constraint = identity_belongs_to_root(&context_identity, &context_identity.key(), &root_identity.key()) @ PrismError::Unauthorized,
+        constraint = root_identity.version == ROOT_IDENTITY_VERSION @ PrismError::UnsupportedVersion
+    )]
+    pub root_identity: Account<'info, RootIdentity>,
+}
+
+#[derive(Accounts)]
+pub struct GetArchive<'info> {
+    pub context_archive: Account<'info, ContextArchive>,
+}
+
+#[derive(Accounts)]
+pub struct ValidateStateConsistency<'info> {
+    #[account(constraint = context_identity.version == CONTEXT_IDENTITY_VERSION @ PrismError::UnsupportedVersion)]
+    pub context_identity: Account<'info, ContextIdentity>,
+}
+
+#[derive(Accounts)]
+pub struct SetSpendMultiple<'info> {
+    #[account(mut)]
+    pub user: Signer<'info>,
+    
+    #[account(
+        seeds = [b"root", root_identity.creator.as_ref()],
+        bump = root_identity.bump,
+        constraint = root_identity.is_owner(&user.key()) @ PrismError::Unauthorized,
+        constraint = root_identity.version == ROOT_IDENTITY_VERSION @ PrismError::UnsupportedVersion
+    )]
+    pub root_identity: Account<'info, RootIdentity>,
+    
+    #[account(
+        mut,
+        seeds = [
+            context_identity.seed_prefix(&root_identity.key()),
+            &context_identity.seed_key(&root_identity.key()),
+            &context_identity.context_index.to_le_bytes()
+        ],
+        bump = context_identity.bump,
+        constraint = context_identity.version == CONTEXT_IDENTITY_VERSION @ PrismError::UnsupportedVersion
+    )]
+    pub context_identity: Account<'info, ContextIdentity>,
+    
+    #[account(
+        seeds = [b"config"],
+        bump = config.bump,
+        constraint = !config.paused @ PrismError::ProgramPaused
+    )]
+    pub config: Account<'info, ProgramConfig>,
+}
+
+#[derive(Accounts)]
+pub struct AttestActive<'info> {
+    #[account(
+        seeds = [
+            context_identity.seed_prefix(&root_identity.key()),
+            &context_identity.seed_key(&root_identity.key()),
+            &context_identity.context_index.to_le_bytes()
+        ],
+        bump = context_identity.bump,
+        constraint = context_identity.version == CONTEXT_IDENTITY_VERSION @ PrismError::UnsupportedVersion
+    )]
+    pub context_identity: Account<'info, ContextIdentity>,
+    
+    // Needed to check the root isn't frozen; also derives the PDA for encrypted contexts
+    #[account(
+        seeds = [b"root", root_identity.creator.as_ref()],
+        bump = root_identity.bump,
+        constraint = root_identity.version == ROOT_IDENTITY_VERSION @ PrismError::UnsupportedVersion
+    )]
+    pub root_identity: Account<'info, RootIdentity>,
+}
+
+#[derive(Accounts)]
+pub struct CloseExpiredContext<'info> {
+    pub caller: Signer<'info>,
+    
+    #[account(
+        mut,
+        seeds = [b"root", root_identity.creator.as_ref()],
+        bump = root_identity.bump,
+        constraint = root_identity.version == ROOT_IDENTITY_VERSION @ PrismError::UnsupportedVersion
+    )]
+    pub root_identity: Account<'info, RootIdentity>,
+    
+    #[account(
+        mut,
+        close = rent_destination,
+        seeds = [
+            context_identity.seed_prefix(&root_identity.key()),
+            &context_identity.seed_key(&root_identity.key()),
+            &context_identity.context_index.to_le_bytes()
+        ],
+        bump = context_identity.bump,
+        constraint = context_identity.version == CONTEXT_IDENTITY_VERSION @ PrismError::UnsupportedVersion
+    )]
+    pub context_identity: Account<'info, ContextIdentity>,
+
+    /// Rent always goes back to the root owner, never the caller
+    #[account(mut, address = root_identity.owner @ PrismError::Unauthorized)]
+    pub rent_destination: SystemAccount<'info>,
+    
+    #[account(
+        seeds = [b"config"],
+        bump = config.bump,
+        constraint = !config.paused @ PrismError::ProgramPaused
+    )]
+    pub config: Account<'info, ProgramConfig>,
+}
+
+#[derive(Accounts)]
+pub struct SetEmergencyContact<'info> {
+    #[account(mut)]
+    pub user: Signer<'info>,
+    
+    #[account(
+        mut,
+        seeds = [b"root", root_identity.creator.as_ref()],
+        bump = root_identity.bump,
+        constraint = root_identity.is_owner(&user.key()) @ PrismError::Unauthorized,
+        constraint = root_identity.version == ROOT_IDENTITY_VERSION @ PrismError::UnsupportedVersion
+    )]
+    pub root_identity: Account<'info, RootIdentity>,
+    
+    #[account(
+        seeds = [b"config"],
+        bump = config.bump,
+        constraint = !config.paused @ PrismError::ProgramPaused
+    )]
+    pub config: Account<'info, ProgramConfig>,
+}
+
+#[derive(Accounts)]
+pub struct ComputeEffectivePrivacy<'info> {
+    #[account(constraint = root_identity.version == ROOT_IDENTITY_VERSION @ PrismError::UnsupportedVersion)]
+    pub root_identity: Account<'info, RootIdentity>,
+}
+
+#[derive(Accounts)]
+pub struct SetWindowLimit<'info> {
+    #[account(mut)]
+    pub user: Signer<'info>,
+    
+    #[account(
+        seeds = [b"root", root_identity.creator.as_ref()],
+        bump = root_identity.bump,
+        constraint = root_identity.is_owner(&user.key()) @ PrismError::Unauthorized,
+        constraint = root_identity.version == ROOT_IDENTITY_VERSION @ PrismError::UnsupportedVersion
+    )]
+    pub root_identity: Account<'info, RootIdentity>,
+    
+    #[account(
+        mut,
+        seeds = [
+            context_identity.seed_prefix(&root_identity.key()),
+            &context_identity.seed_key(&root_identity.key()),
+            &context_identity.context_index.to_le_bytes()
+        ],
+        bump = context_identity.bump,
+        constraint = context_identity.version == CONTEXT_IDENTITY_VERSION @ PrismError::UnsupportedVersion
+    )]
+    pub context_identity: Account<'info, ContextIdentity>,
+    
+    #[account(
+        seeds = [b"config"],
+        bump = config.bump,
+        constraint = !config.paused @ PrismError::ProgramPaused
+    )]
+    pub config: Account<'info, ProgramConfig>,
+}
+
+#[derive(Accounts)]
+pub struct SetReactivationCooldown<'info> {
+    #[account(mut)]
+    pub user: Signer<'info>,
+    
+    #[account(
+        mut,
+        seeds = [b"root", root_identity.creator.as_ref()],
+        bump = root_identity.bump,
+        constraint = root_identity.is_owner(&user.key()) @ PrismError::Unauthorized,
+        constraint = root_identity.version == ROOT_IDENTITY_VERSION @ PrismError::UnsupportedVersion
+    )]
+    pub root_identity: Account<'info, RootIdentity>,
+    
+    #[account(
+        seeds = [b"config"],
+        bump = config.bump,
+        constraint = !config.paused @ PrismError::ProgramPaused
+    )]
+    pub config: Account<'info, ProgramConfig>,
+}
+
+#[derive(Accounts)]
+pub struct ReactivateContext<'info> {
+    #[account(mut)]
+    pub user: Signer<'info>,
+    
+    #[account(
+        mut,
+        seeds = [b"root", root_identity.creator.as_ref()],
+        bump = root_identity.bump,
+        constraint = root_identity.is_owner(&user.key()) @ PrismError::Unauthorized,
+        constraint = root_identity.version == ROOT_IDENTITY_VERSION @ PrismError::UnsupportedVersion
+    )]
+    pub root_identity: Account<'info, RootIdentity>,
+    
+    #[account(
+        mut,
+        seeds = [
+            context_identity.seed_prefix(&root_identity.key()),
+            &context_identity.seed_key(&root_identity.key()),
+            &context_identity.context_index.to_le_bytes()
+        ],
+        bump = context_identity.bump,
+        constraint = context_identity.version == CONTEXT_IDENTITY_VERSION @ PrismError::UnsupportedVersion
+    )]
+    pub context_identity: Account<'info, ContextIdentity>,
+    
+    #[account(
+        seeds = [b"config"],
+        bump = config.bump,
+        constraint = !config.paused @ PrismError::ProgramPaused
+    )]
+    pub config: Account<'info, ProgramConfig>,
+}
+
+#[derive(Accounts)]
+pub struct ProposeOwner<'info> {
+    #[account(mut)]
+    pub user: Signer<'info>,
+    
+    #[account(
+        mut,
+        seeds = [b"root", root_identity.creator.as_ref()],
+        bump = root_identity.bump,
+        constraint = root_identity.is_owner(&user.key()) @ PrismError::Unauthorized,
+        constraint = root_identity.version == ROOT_IDENTITY_VERSION @ PrismError::UnsupportedVersion
+    )]
+    pub root_identity: Account<'info, RootIdentity>,
+    
+    #[account(
+        seeds = [b"config"],
+        bump = config.bump,
+        constraint = !config.paused @ PrismError::ProgramPaused
+    )]
+    pub config: Account<'info, ProgramConfig>,
+}
+
+#[derive(Accounts)]
+pub struct AcceptOwnership<'info> {
+    #[account(mut)]
+    pub new_owner: Signer<'info>,
+    
+    #[account(
+        mut,
+        seeds = [b"root", root_identity.creator.as_ref()],
+        bump = root_identity.bump,
+        constraint = root_identity.pending_owner == Some(new_owner.key()) @ PrismError::Unauthorized,
+        constraint = root_identity.version == ROOT_IDENTITY_VERSION @ PrismError::UnsupportedVersion
+    )]
+    pub root_identity: Account<'info, RootIdentity>,
+    
+    #[account(
+        seeds = [b"config"],
+        bump = config.bump,
+        constraint = !config.paused @ PrismError::ProgramPaused
+    )]
+    pub config: Account<'info, ProgramConfig>,
+}
+
+#[derive(Accounts)]
+pub struct CancelOwnershipTransfer<'info> {
+    #[account(mut)]
+    pub user: Signer<'info>,
+    
+    #[account(
+        mut,
+        seeds = [b"root", root_identity.creator.as_ref()],
+        bump = root_identity.bump,
+        constraint = root_identity.is_owner(&user.key()) @ PrismError::Unauthorized,
+        constraint = root_identity.version == ROOT_IDENTITY_VERSION @ PrismError::UnsupportedVersion
+    )]
+    pub root_identity: Account<'info, RootIdentity>,
+}
+
+#[derive(Accounts)]
+pub struct SetGlobalSpendLimit<'info> {
+    #[account(mut)]
+    pub user: Signer<'info>,
+    
+    #[account(
+        mut,
+        seeds = [b"root", root_identity.creator.as_ref()],
+        bump = root_identity.bump,
+        constraint = root_identity.is_owner(&user.key()) @ PrismError::Unauthorized,
+        constraint = root_identity.version == ROOT_IDENTITY_VERSION @ PrismError::UnsupportedVersion
+    )]
+    pub root_identity: Account<'info, RootIdentity>,
+    
+    #[account(
+        seeds = [b"config"],
+        bump = config.bump,
+        constraint = !config.paused @ PrismError::ProgramPaused
+    )]
+    pub config: Account<'info, ProgramConfig>,
+}
+
+#[derive(Accounts)]
+pub struct CreateContextsBatch<'info> {
+    #[account(mut)]
+    pub user: Signer<'info>,
+    
+    #[account(
+        mut,
+        seeds = [b"root", root_identity.creator.as_ref()],
+        bump = root_identity.bump,
+        constraint = root_identity.is_owner(&user.key()) @ PrismError::Unauthorized,
+        constraint = root_identity.version == ROOT_IDENTITY_VERSION @ PrismError::UnsupportedVersion
+    )]
+    pub root_identity: Account<'info, RootIdentity>,
+    
+    pub system_program: Program<'info, System>,
+    
+    #[account(
+        seeds = [b"config"],
+        bump = config.bump,
+        constraint = !config.paused @ PrismError::ProgramPaused
+    )]
+    pub config: Account<'info, ProgramConfig>,
+}
+
+#[derive(Accounts)]
+pub struct RevokeAllContexts<'info> {
+    #[account(mut)]
+    pub user: Signer<'info>,
+    
+    #[account(
+        mut,
+        seeds = [b"root", root_identity.creator.as_ref()],
+        bump = root_identity.bump,
+        constraint = root_identity.is_owner(&user.key()) @ PrismError::Unauthorized,
+        constraint = root_identity.version == ROOT_IDENTITY_VERSION @ PrismError::UnsupportedVersion
+    )]
+    pub root_identity: Account<'info, RootIdentity>,
+}
+
+#[derive(Accounts)]
+pub struct FreezeRoot<'info> {
+    #[account(mut)]
+    pub user: Signer<'info>,
+    
+    #[account(
+        mut,
+        seeds = [b"root", root_identity.creator.as_ref()],
+        bump = root_identity.bump,
+        constraint = root_identity.is_owner(&user.key()) @ PrismError::Unauthorized,
+        constraint = root_identity.version == ROOT_IDENTITY_VERSION @ PrismError::UnsupportedVersion
+    )]
+    pub root_identity: Account<'info, RootIdentity>,
+}
+
+#[derive(Accounts)]
+pub struct SetGuardians<'info> {
+    #[account(mut)]
+    pub user: Signer<'info>,
+    
+    #[account(
+        mut,
+        seeds = [b"root", root_identity.creator.as_ref()],
+        bump = root_identity.bump,
+        constraint = root_identity.is_owner(&user.key()) @ PrismError::Unauthorized,
+        constraint = root_identity.version == ROOT_IDENTITY_VERSION @ PrismError::UnsupportedVersion
+    )]
+    pub root_identity: Account<'info, RootIdentity>,
+    
+    #[account(
+        seeds = [b"config"],
+        bump = config.bump,
+        constraint = !config.paused @ PrismError::ProgramPaused
+    )]
+    pub config: Account<'info, ProgramConfig>,
+}
+
+#[derive(Accounts)]
+pub struct GuardianRecovery<'info> {
+    pub guardian: Signer<'info>,
+    
+    #[account(
+        mut,
+        seeds = [b"root", root_identity.creator.as_ref()],
+        bump = root_identity.bump,
+        constraint = root_identity.version == ROOT_IDENTITY_VERSION @ PrismError::UnsupportedVersion
+    )]
+    pub root_identity: Account<'info, RootIdentity>,
+    
+    #[account(
+        seeds = [b"config"],
+        bump = config.bump,
+        constraint = !config.paused @ PrismError::ProgramPaused
+    )]
+    pub config: Account<'info, ProgramConfig>,
+}
+
+#[derive(Accounts)]
+pub struct ExecuteRecovery<'info> {
+    pub caller: Signer<'info>,
+    
+    #[account(
+        mut,
+        seeds = [b"root", root_identity.creator.as_ref()],
+        bump = root_identity.bump,
+        constraint = root_identity.version == ROOT_IDENTITY_VERSION @ PrismError::UnsupportedVersion
+    )]
+    pub root_identity: Account<'info, RootIdentity>,
+    
+    #[account(
+        seeds = [b"config"],
+        bump = config.bump,
+        constraint = !config.paused @ PrismError::ProgramPaused
+    )]
+    pub config: Account<'info, ProgramConfig>,
+}
+
+#[derive(Accounts)]
+pub struct GetContextStatus<'info> {
+    #[account(constraint = context_identity.version == CONTEXT_IDENTITY_VERSION @ PrismError::UnsupportedVersion)]
+    pub context_identity: Account<'info, ContextIdentity>,
+}
+
+#[derive(Accounts)]
+pub struct MigrateAccount<'info> {
+    #[account(mut)]
+    pub payer: Signer<'info>,
+
+    /// Owner of the root being migrated, or of the root the context belongs to
+    pub owner: Signer<'info>,
+
+    /// CHECK: owner is checked here; the layout is detected and decoded in the handler
+    #[account(mut, owner = crate::ID @ PrismError::UnsupportedVersion)]
+    pub account: UncheckedAccount<'info>,
+
+    /// Root of a context being migrated (roots are migrated first); unused for roots
+    #[account(
+        seeds = [b"root", root_identity.creator.as_ref()],
+        bump = root_identity.bump,
+        constraint = root_identity.version == ROOT_IDENTITY_VERSION @ PrismError::UnsupportedVersion
+    )]
+    pub root_identity: Option<Account<'info, RootIdentity>>,
+    
+    pub system_program: Program<'info, System>,
+    
+    #[account(
+        seeds = [b"config"],
+        bump = config.bump,
+        constraint = !config.paused @ PrismError::ProgramPaused
+    )]
+    pub config: Account<'info, ProgramConfig>,
+}
+
+#[derive(Accounts)]
+#[instruction(context_index: u16)]
+pub struct ResizeContext<'info> {
+    #[account(mut)]
+    pub user: Signer<'info>,
+    
+    #[account(
+        seeds = [b"root", root_identity.creator.as_ref()],
+        bump = root_identity.bump,
+        constraint = root_identity.is_owner(&user.key()) @ PrismError::Unauthorized,
+        constraint = root_identity.version == ROOT_IDENTITY_VERSION @ PrismError::UnsupportedVersion
+    )]
+    pub root_identity: Account<'info, RootIdentity>,
+
+    /// CHECK: PDA of this root, owner checked here, discriminator checked in the handler
+    #[account(
+        mut,
+        owner = crate::ID @ PrismError::ContextMismatch,
+        seeds = [
+            b"context",
+            root_identity.key().as_ref(),
+            &context_index.to_le_bytes()
+        ],
+        bump
+    )]
+    pub context_identity: UncheckedAccount<'info>,
+    
+    pub system_program: Program<'info, System>,
+    
+    #[account(
+        seeds = [b"config"],
+        bump = config.bump,
+        constraint = !config.paused @ PrismError::ProgramPaused
+    )]
+    pub config: Account<'info, ProgramConfig>,
+}
+
+#[derive(Accounts)]
+pub struct UpdateEncryptionCommitment<'info> {
+    #[account(mut)]
+    pub user: Signer<'info>,
+    
+    #[account(
+        seeds = [b"root", root_identity.creator.as_ref()],
+        bump = root_identity.bump,
+        constraint = root_identity.is_owner(&user.key()) @ PrismError::Unauthorized,
+        constraint = root_identity.version == ROOT_IDENTITY_VERSION @ PrismError::UnsupportedVersion
+    )]
+    pub root_identity: Account<'info, RootIdentity>,
+    
+    #[account(
+        mut,
+        seeds = [
+            context_identity.seed_prefix(&root_identity.key()),
+            &context_identity.seed_key(&root_identity.key()),
+            &context_identity.context_index.to_le_bytes()
+        ],
+        bump = context_identity.bump,
+        constraint = context_identity.version == CONTEXT_IDENTITY_VERSION @ PrismError::UnsupportedVersion
+    )]
+    pub context_identity: Account<'info, ContextIdentity>,
+    
+    #[account(
+        seeds = [b"config"],
+        bump = config.bump,
+        constraint = !config.paused @ PrismError::ProgramPaused
+    )]
+    pub config: Account<'info, ProgramConfig>,
+}
+
+#[derive(Accounts)]
+pub struct SetTypeDefaults<'info> {
+    #[account(mut)]
+    pub user: Signer<'info>,
+    
+    #[account(
+        mut,
+        seeds = [b"root", root_identity.creator.as_ref()],
+        bump = root_identity.bump,
+        constraint = root_identity.is_owner(&user.key()) @ PrismError::Unauthorized,
+        constraint = root_identity.version == ROOT_IDENTITY_VERSION @ PrismError::UnsupportedVersion
+    )]
+    pub root_identity: Account<'info, RootIdentity>,
+    
+    #[account(
+        seeds = [b"config"],
+        bump = config.bump,
+        constraint = !config.paused @ PrismError::ProgramPaused
+    )]
+    pub config: Account<'info, ProgramConfig>,
+}
+
+#[derive(Accounts)]
+pub struct CancelRecovery<'info> {
+    #[account(mut)]
+    pub user: Signer<'info>,
+    
+    #[account(
+        mut,
+        seeds = [b"root", root_identity.creator.as_ref()],
+        bump = root_identity.bump,
+        constraint = root_identity.is_owner(&user.key()) @ PrismError::Unauthorized,
+        constraint = root_identity.version == ROOT_IDENTITY_VERSION @ PrismError::UnsupportedVersion
+    )]
+    pub root_identity: Account<'info, RootIdentity>,
+}
+
+#[derive(Accounts)]
+pub struct InitializeConfig<'info> {
+    #[account(mut)]
+    pub admin: Signer<'info>,
+    
+    #[account(
+        init,
+        payer = admin,
+        space = ProgramConfig::SIZE,
+        seeds = [b"config"],
+        bump
+    )]
+    pub config: Account<'info, ProgramConfig>,
+    
+    #[account(constraint = program.programdata_address()? == Some(program_data.key()) @ PrismError::Unauthorized)]
+    pub program: Program<'info, crate::program::Prism>,
+    
+    #[account(constraint = program_data.upgrade_authority_address == Some(admin.key()) @ PrismError::Unauthorized)]
+    pub program_data: Account<'info, ProgramData>,
+    
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct SetConfigAdmin<'info> {
+    pub admin: Signer<'info>,
+    
+    pub new_admin: Signer<'info>,
+    
+    #[account(
+        mut,
+        seeds = [b"config"],
+        bump = config.bump,
+        constraint = config.admin == admin.key() @ PrismError::Unauthorized
+    )]
+    pub config: Account<'info, ProgramConfig>,
+}
+
+#[derive(Accounts)]
+pub struct SetPaused<'info> {
+    pub admin: Signer<'info>,
+    
+    #[account(
+        mut,
+        seeds = [b"config"],
+        bump = config.bump,
+        constraint = config.admin == admin.key() @ PrismError::Unauthorized
+    )]
+    pub config: Account<'info, ProgramConfig>,
+}
+
+#[derive(Accounts)]
+pub struct SetVerboseEvents<'info> {
+    pub admin: Signer<'info>,
+    
+    #[account(
+        mut,
+        seeds = [b"config"],
+        bump = config.bump,
+        constraint = config.admin == admin.key() @ PrismError::Unauthorized
+    )]
+    pub config: Account<'info, ProgramConfig>,
+}
+
+#[derive(Accounts)]
+pub struct CreateSubContext<'info> {
+    #[account(mut)]
+    pub user: Signer<'info>,
+    
+    #[account(
+        mut,
+        seeds = [b"root", root_identity.creator.as_ref()],
+        bump = root_identity.bump,
+        constraint = root_identity.is_owner(&user.key()) @ PrismError::Unauthorized,
+        constraint = root_identity.version == ROOT_IDENTITY_VERSION @ PrismError::UnsupportedVersion
+    )]
+    pub root_identity: Account<'info, RootIdentity>,
+    
+    #[account(
+        mut,
+        seeds = [
+            parent_context.seed_prefix(&root_identity.key()),
+            &parent_context.seed_key(&root_identity.key()),
+            &parent_context.context_index.to_le_bytes()
+        ],
+        bump = parent_context.bump,
+        constraint = parent_context.version == CONTEXT_IDENTITY_VERSION @ PrismError::UnsupportedVersion
+    )]
+    pub parent_context: Account<'info, ContextIdentity>,
+    
+    #[account(
+        init,
+        payer = user,
+        space = ContextIdentity::SIZE,
+        seeds = [
+            b"sub_context",
+            parent_context.key().as_ref(),
+            &parent_context.child_count.to_le_bytes()
+        ],
+        bump
+    )]
+    pub sub_context: Account<'info, ContextIdentity>,
+    
+    pub system_program: Program<'info, System>,
+    
+    #[account(
+        seeds = [b"config"],
+        bump = config.bump,
+        constraint = !config.paused @ PrismError::ProgramPaused
+    )]
+    pub config: Account<'info, ProgramConfig>,
+}
+
+#[derive(Accounts)]
+pub struct RecordSubSpending<'info> {
+    #[account(mut)]
+    pub user: Signer<'info>,
+    
+    #[account(
+        mut,
+        seeds = [b"root", root_identity.creator.as_ref()],
+        bump = root_identity.bump,
+        constraint = root_identity.is_owner(&user.key()) @ PrismError::Unauthorized,
+        constraint = root_identity.version == ROOT_IDENTITY_VERSION @ PrismError::UnsupportedVersion
+    )]
+    pub root_identity: Account<'info, RootIdentity>,
+    
+    #[account(
+        mut,
+        seeds = [
+            parent_context.seed_prefix(&root_identity.key()),
+            &parent_context.seed_key(&root_identity.key()),
+            &parent_context.context_index.to_le_bytes()
+        ],
+        bump = parent_context.bump,
+        constraint = parent_context.version == CONTEXT_IDENTITY_VERSION @ PrismError::UnsupportedVersion
+    )]
+    pub parent_context: Account<'info, ContextIdentity>,
+
+    /// Token-denominated sub-contexts are not supported yet
+    #[account(
+        mut,
+        seeds = [
+            b"sub_context",
+            parent_context.key().as_ref(),
+            &sub_context.context_index.to_le_bytes()
+        ],
+        bump = sub_context.bump,
+        constraint = sub_context.parent_context == Some(parent_context.key()) @ PrismError::ContextMismatch,
+        constraint = sub_context.mint.is_none() @ PrismError::MintMismatch,
+        constraint = sub_context.version == CONTEXT_IDENTITY_VERSION @ PrismError::UnsupportedVersion
+    )]
+    pub sub_context: Account<'info, ContextIdentity>,
+    
+    #[account(
+        seeds = [b"config"],
+        bump = config.bump,
+        constraint = !config.paused @ PrismError::ProgramPaused
+    )]
+    pub config: Account<'info, ProgramConfig>,
+}
+
+#[derive(Accounts)]
+pub struct RevokeSubContext<'info> {
+    #[account(mut)]
+    pub user: Signer<'info>,
+    
+    #[account(
+        seeds = [b"root", root_identity.creator.as_ref()],
+        bump = root_identity.bump,
+        constraint = root_identity.is_owner(&user.key()) @ PrismError::Unauthorized,
+        constraint = root_identity.version == ROOT_IDENTITY_VERSION @ PrismError::UnsupportedVersion
+    )]
+    pub root_identity: Account<'info, RootIdentity>,
+    
+    #[account(
+        mut,
+        seeds = [
+            parent_context.seed_prefix(&root_identity.key()),
+            &parent_context.seed_key(&root_identity.key()),
+            &parent_context.context_index.to_le_bytes()
+        ],
+        bump = parent_context.bump,
+        constraint = parent_context.version == CONTEXT_IDENTITY_VERSION @ PrismError::UnsupportedVersion
+    )]
+    pub parent_context: Account<'info, ContextIdentity>,
+    
+    #[account(
+        mut,
+        seeds = [
+            b"sub_context",
+            parent_context.key().as_ref(),
+            &sub_context.context_index.to_le_bytes()
+        ],
+        bump = sub_context.bump,
+        constraint = sub_context.parent_context == Some(parent_context.key()) @ PrismError::ContextMismatch,
+        constraint = sub_context.version == CONTEXT_IDENTITY_VERSION @ PrismError::UnsupportedVersion
+    )]
+    pub sub_context: Account<'info, ContextIdentity>,
+}
+
+#[derive(Accounts)]
+pub struct SetVerifierKey<'info> {
+    #[account(mut)]
+    pub user: Signer<'info>,
+    
+    #[account(
+        seeds = [b"root", root_identity.creator.as_ref()],
+        bump = root_identity.bump,
+        constraint = root_identity.is_owner(&user.key()) @ PrismError::Unauthorized,
+        constraint = root_identity.version == ROOT_IDENTITY_VERSION @ PrismError::UnsupportedVersion
+    )]
+    pub root_identity: Account<'info, RootIdentity>,
+    
+    #[account(
+        mut,
+        seeds = [
+            context_identity.seed_prefix(&root_identity.key()),
+            &context_identity.seed_key(&root_identity.key()),
+            &context_identity.context_index.to_le_bytes()
+        ],
+        bump = context_identity.bump,
+        constraint = context_identity.version == CONTEXT_IDENTITY_VERSION @ PrismError::UnsupportedVersion
+    )]
+    pub context_identity: Account<'info, ContextIdentity>,
+    
+    #[account(
+        seeds = [b"config"],
+        bump = config.bump,
+        constraint = !config.paused @ PrismError::ProgramPaused
+    )]
+    pub config: Account<'info, ProgramConfig>,
+}
+
+#[derive(Accounts)]
+pub struct RefundSpending<'info> {
+    #[account(mut)]
+    pub user: Signer<'info>,
+    
+    #[account(
+        mut,
+        seeds = [b"root", root_identity.creator.as_ref()],
+        bump = root_identity.bump,
+        constraint = root_identity.is_owner(&user.key()) @ PrismError::Unauthorized,
+        constraint = root_identity.version == ROOT_IDENTITY_VERSION @ PrismError::UnsupportedVersion
+    )]
+    pub root_identity: Account<'info, RootIdentity>,
+    
+    #[account(
+        mut,
+        seeds = [
+            context_identity.seed_prefix(&root_identity.key()),
+            &context_identity.seed_key(&root_identity.key()),
+            &context_identity.context_index.to_le_bytes()
+        ],
+        bump = context_identity.bump,
+        constraint = context_identity.version == CONTEXT_IDENTITY_VERSION @ PrismError::UnsupportedVersion
+    )]
+    pub context_identity: Account<'info, ContextIdentity>,
+    
+    #[account(
+        seeds = [b"config"],
+        bump = config.bump,
+        constraint = !config.paused @ PrismError::ProgramPaused
+    )]
+    pub config: Account<'info, ProgramConfig>,
+}
+
+#[derive(Accounts)]
+pub struct SetContextLabel<'info> {
+    #[account(mut)]
+    pub user: Signer<'info>,
+    
+    #[account(
+        seeds = [b"root", root_identity.creator.as_ref()],
+        bump = root_identity.bump,
+        constraint = root_identity.is_owner(&user.key()) @ PrismError::Unauthorized,
+        constraint = root_identity.version == ROOT_IDENTITY_VERSION @ PrismError::UnsupportedVersion
+    )]
+    pub root_identity: Account<'info, RootIdentity>,
+    
+    #[account(
+        mut,
+        seeds = [
+            context_identity.seed_prefix(&root_identity.key()),
+            &context_identity.seed_key(&root_identity.key()),
+            &context_identity.context_index.to_le_bytes()
+        ],
+        bump = context_identity.bump,
+        constraint = context_identity.version == CONTEXT_IDENTITY_VERSION @ PrismError::UnsupportedVersion
+    )]
+    pub context_identity: Account<'info, ContextIdentity>,
+    
+    #[account(
+        seeds = [b"config"],
+        bump = config.bump,
+        constraint = !config.paused @ PrismError::ProgramPaused
+    )]
+    pub config: Account<'info, ProgramConfig>,
+}
+
+#[derive(Accounts)]
+pub struct CloseRevokedContext<'info> {
+    #[account(mut)]
+    pub user: Signer<'info>,
+    
+    #[account(
+        mut,
+        seeds = [b"root", root_identity.creator.as_ref()],
+        bump = root_identity.bump,
+        constraint = root_identity.is_owner(&user.key()) @ PrismError::Unauthorized,
+        constraint = root_identity.version == ROOT_IDENTITY_VERSION @ PrismError::UnsupportedVersion
+    )]
+    pub root_identity: Account<'info, RootIdentity>,
+    
+    // Contexts and sub-contexts alike, so checked by derivation rather than fixed seeds
+    #[account(
+        mut,
+        close = user,
+        constraint = identity_belongs_to_root(&context_identity, &context_identity.key(), &root_identity.key()) @ PrismError::ContextMismatch,
+        constraint = context_identity.version == CONTEXT_IDENTITY_VERSION @ PrismError::UnsupportedVersion
+    )]
+    pub context_identity: Account<'info, ContextIdentity>,
+    
+    #[account(
+        seeds = [b"config"],
+        bump = config.bump,
+        constraint = !config.paused @ PrismError::ProgramPaused
+    )]
+    pub config: Account<'info, ProgramConfig>,
+}
+
+#[derive(Accounts)]
+pub struct CloseRootIdentity<'info> {
+    #[account(mut)]
+    pub user: Signer<'info>,
+    
+    #[account(
+        mut,
+        close = user,
+        seeds = [b"root", root_identity.creator.as_ref()],
+        bump = root_identity.bump,
+        constraint = root_identity.is_owner(&user.key()) @ PrismError::Unauthorized,
+        constraint = root_identity.version == ROOT_IDENTITY_VERSION @ PrismError::UnsupportedVersion
+    )]
+    pub root_identity: Account<'info, RootIdentity>,
+    
+    #[account(
+        seeds = [b"config"],
+        bump = config.bump,
+        constraint = !config.paused @ PrismError::ProgramPaused
+    )]
+    pub config: Account<'info, ProgramConfig>,
+}
+
+#[derive(Accounts)]
+#[instruction(root_identity_hash: [u8; 32])]
+pub struct VerifyCommitmentEncrypted<'info> {
+    /// Encrypted contexts can't be re-derived from seeds without the root; program
+    /// ownership and the discriminator are enough to trust the account's contents
+    #[account(
+        constraint = context_identity.root_identity_hash == Some(root_identity_hash) @ PrismError::ContextMismatch,
+        constraint = context_identity.version == CONTEXT_IDENTITY_VERSION @ PrismError::UnsupportedVersion
+    )]
+    pub context_identity: Account<'info, ContextIdentity>,
+
+    /// CHECK: Instructions sysvar, validated by address
+    #[account(address = anchor_lang::solana_program::sysvar::instructions::ID)]
+    pub instructions: UncheckedAccount<'info>,
+}
+
+#[derive(Accounts)]
+#[instruction(root_identity_hash: [u8; 32])]
+pub struct CheckSpendingLimitEncrypted<'info> {
+    /// Encrypted contexts can't be re-derived from seeds without the root; program
+    /// ownership and the discriminator are enough to trust the account's contents
+    #[account(
+        constraint = context_identity.root_identity_hash == Some(root_identity_hash) @ PrismError::ContextMismatch,
+        constraint = context_identity.version == CONTEXT_IDENTITY_VERSION @ PrismError::UnsupportedVersion
+    )]
+    pub context_identity: Account<'info, ContextIdentity>,
+}
+
+#[derive(Accounts)]
+pub struct SetEpochLimit<'info> {
+    #[account(mut)]
+    pub user: Signer<'info>,
+    
+    #[account(
+        seeds = [b"root", root_identity.creator.as_ref()],
+        bump = root_identity.bump,
+        constraint = root_identity.is_owner(&user.key()) @ PrismError::Unauthorized,
+        constraint = root_identity.version == ROOT_IDENTITY_VERSION @ PrismError::UnsupportedVersion
+    )]
+    pub root_identity: Account<'info, RootIdentity>,
+    
+    #[account(
+        mut,
+        seeds = [
+            context_identity.seed_prefix(&root_identity.key()),
+            &context_identity.seed_key(&root_identity.key()),
+            &context_identity.context_index.to_le_bytes()
+        ],
+        bump = context_identity.bump,
+        constraint = context_identity.version == CONTEXT_IDENTITY_VERSION @ PrismError::UnsupportedVersion
+    )]
+    pub context_identity: Account<'info, ContextIdentity>,
+    
+    #[account(
+        seeds = [b"config"],
+        bump = config.bump,
+        constraint = !config.paused @ PrismError::ProgramPaused
+    )]
+    pub config: Account<'info, ProgramConfig>,
+}
+
+#[derive(Accounts)]
+pub struct UpdateContextsMerkleRoot<'info> {
+    #[account(mut)]
+    pub user: Signer<'info>,
+    
+    #[account(
+        mut,
+        seeds = [b"root", root_identity.creator.as_ref()],
+        bump = root_identity.bump,
+        constraint = root_identity.is_owner(&user.key()) @ PrismError::Unauthorized,
+        constraint = root_identity.version == ROOT_IDENTITY_VERSION @ PrismError::UnsupportedVersion
+    )]
+    pub root_identity: Account<'info, RootIdentity>,
+    
+    #[account(
+        seeds = [b"config"],
+        bump = config.bump,
+        constraint = !config.paused @ PrismError::ProgramPaused
+    )]
+    pub config: Account<'info, ProgramConfig>,
+}
+
+#[derive(Accounts)]
+pub struct VerifyContextInclusion<'info> {
+    #[account(
+        seeds = [b"root", root_identity.creator.as_ref()],
+        bump = root_identity.bump,
+        constraint = root_identity.version == ROOT_IDENTITY_VERSION @ PrismError::UnsupportedVersion
+    )]
+    pub root_identity: Account<'info, RootIdentity>,
+}
+
+#[derive(Accounts)]
+pub struct SetContextDelegate<'info> {
+    #[account(mut)]
+    pub user: Signer<'info>,
+    
+    #[account(
+        seeds = [b"root", root_identity.creator.as_ref()],
+        bump = root_identity.bump,
+        constraint = root_identity.is_owner(&user.key()) @ PrismError::Unauthorized,
+        constraint = root_identity.version == ROOT_IDENTITY_VERSION @ PrismError::UnsupportedVersion
+    )]
+    pub root_identity: Account<'info, RootIdentity>,
+    
+    #[account(
+        mut,
+        seeds = [
+            context_identity.seed_prefix(&root_identity.key()),
+            &context_identity.seed_key(&root_identity.key()),
+            &context_identity.context_index.to_le_bytes()
+        ],
+        bump = context_identity.bump,
+        constraint = context_identity.version == CONTEXT_IDENTITY_VERSION @ PrismError::UnsupportedVersion
+    )]
+    pub context_identity: Account<'info, ContextIdentity>,
+    
+    #[account(
+        seeds = [b"config"],
+        bump = config.bump,
+        constraint = !config.paused @ PrismError::ProgramPaused
+    )]
+    pub config: Account<'info, ProgramConfig>,
+}
+
+#[derive(Accounts)]
+pub struct SetMaxSpendCount<'info> {
+    #[account(mut)]
+    pub user: Signer<'info>,
+    
+    #[account(
+        seeds = [b"root", root_identity.creator.as_ref()],
+        bump = root_identity.bump,
+        constraint = root_identity.is_owner(&user.key()) @ PrismError::Unauthorized,
+        constraint = root_identity.version == ROOT_IDENTITY_VERSION @ PrismError::UnsupportedVersion
+    )]
+    pub root_identity: Account<'info, RootIdentity>,
+    
+    #[account(
+        mut,
+        seeds = [
+            context_identity.seed_prefix(&root_identity.key()),
+            &context_identity.seed_key(&root_identity.key()),
+            &context_identity.context_index.to_le_bytes()
+        ],
+        bump = context_identity.bump,
+        constraint = context_identity.version == CONTEXT_IDENTITY_VERSION @ PrismError::UnsupportedVersion
+    )]
+    pub context_identity: Account<'info, ContextIdentity>,
+    
+    #[account(
+        seeds = [b"config"],
+        bump = config.bump,
+        constraint = !config.paused @ PrismError::ProgramPaused
+    )]
+    pub config: Account<'info, ProgramConfig>,
+}
+
+#[derive(Accounts)]
+pub struct SetCreationCooldown<'info> {
+    #[account(mut)]
+    pub user: Signer<'info>,
+    
+    #[account(
+        mut,
+        seeds = [b"root", root_identity.creator.as_ref()],
+        bump = root_identity.bump,
+        constraint = root_identity.is_owner(&user.key()) @ PrismError::Unauthorized,
+        constraint = root_identity.version == ROOT_IDENTITY_VERSION @ PrismError::UnsupportedVersion
+    )]
+    pub root_identity: Account<'info, RootIdentity>,
+    
+    #[account(
+        seeds = [b"config"],
+        bump = config.bump,
+        constraint = !config.paused @ PrismError::ProgramPaused
+    )]
+    pub config: Account<'info, ProgramConfig>,
+}
+
+#[derive(Accounts)]
+pub struct UpdateContextLimit<'info> {
+    #[account(mut)]
+    pub user: Signer<'info>,
+    
+    #[account(
+        seeds = [b"root", root_identity.creator.as_ref()],
+        bump = root_identity.bump,
+        constraint = root_identity.is_owner(&user.key()) @ PrismError::Unauthorized,
+        constraint = root_identity.version == ROOT_IDENTITY_VERSION @ PrismError::UnsupportedVersion
+    )]
+    pub root_identity: Account<'info, RootIdentity>,
+    
+    #[account(
+        mut,
+        seeds = [
+            context_identity.seed_prefix(&root_identity.key()),
+            &context_identity.seed_key(&root_identity.key()),
+            &context_identity.context_index.to_le_bytes()
+        ],
+        bump = context_identity.bump,
+        constraint = context_identity.version == CONTEXT_IDENTITY_VERSION @ PrismError::UnsupportedVersion
+    )]
+    pub context_identity: Account<'info, ContextIdentity>,
+    
+    #[account(
+        seeds = [b"config"],
+        bump = config.bump,
+        constraint = !config.paused @ PrismError::ProgramPaused
+    )]
+    pub config: Account<'info, ProgramConfig>,
+}
+
+// ============================================================================
+// ACCOUNT STRUCTS
+// ============================================================================
+
+#[account]
+#[derive(Default)]
+pub struct RootIdentity {
+    pub version: u8,             // 1 byte   - layout version (ROOT_IDENTITY_VERSION)
+    pub owner: Pubkey,           // 32 bytes - wallet that owns this identity
+    pub creator: Pubkey,         // 32 bytes - wallet that created it (PDA seed, never changes)
+    pub pending_owner: Option<Pubkey>, // 33 bytes - proposed owner awaiting acceptance
+    pub created_at: i64,         // 8 bytes  - unix timestamp
+    pub privacy_level: u8,       // 1 byte   - 0=Maximum, 1=High, 2=Medium, 3=Low, 4=Public
+    pub context_count: u16,      // 2 bytes  - number of contexts created
+    pub max_contexts: u16,       // 2 bytes  - cap on context_count
+    pub encryption_epoch: u32,   // 4 bytes  - current epoch for grouping encrypted contexts
+    pub privacy_policy_hash: Option<[u8; 32]>, // 33 bytes - optional hash of a committed privacy policy
+    pub emergency_contact_hash: [u8; 32], // 32 bytes - hash of emergency contacts (zero = none)
+    pub reactivation_cooldown: i64, // 8 bytes  - seconds a context must stay revoked before reactivation
+    pub global_spend_limit: u64, // 8 bytes  - cap on lamports spent across all contexts (0 = unlimited)
+    pub global_spent: u64,       // 8 bytes  - lamports spent across all contexts
+    pub frozen: bool,            // 1 byte   - emergency freeze halting all context spending
+    pub guardians: [Pubkey; MAX_GUARDIANS], // 96 bytes - recovery guardians (default pubkey = empty slot)
+    pub recovery_threshold: u8,  // 1 byte   - guardian approvals needed to recover (0 = disabled)
+    pub recovery_new_owner: Option<Pubkey>, // 33 bytes - owner proposed by a pending recovery
+    pub recovery_initiated_at: i64, // 8 bytes  - unix timestamp the pending recovery started
+    pub recovery_approvals: u8,  // 1 byte   - bitmask of guardian slots that approved
+    pub bump: u8,                // 1 byte   - PDA bump seed
+    // Fields below were appended after versioning; see ROOT_IDENTITY_VERSION
+    pub revoked_count: u16,      // 2 bytes  - contexts currently revoked (active = context_count - revoked_count) [v2]
+    pub type_defaults: [u64; 6], // 48 bytes - default max_per_transaction per context type [v3]
+    pub contexts_merkle_root: [u8; 32], // 32 bytes - owner-maintained Merkle root over context keys (zero = unset) [v4]
+    pub pending_privacy_level: Option<u8>, // 2 bytes - proposed lower privacy level awaiting its timelock [v5]
+    pub privacy_change_eta: i64, // 8 bytes  - unix timestamp the pending downgrade can be finalized at [v5]
+    pub privacy_downgrade_timelock: i64, // 8 bytes - delay for privacy downgrades (0 = default) [v5]
+    pub last_context_created_at: i64, // 8 bytes - unix timestamp of the latest context creation [v6]
+    pub creation_cooldown: i64,  // 8 bytes  - minimum seconds between context creations (0 = none) [v6]
+    pub owners: [Pubkey; MAX_OWNERS], // 160 bytes - multisig owners (default pubkey = empty slot) [v7]
+    pub owner_count: u8,         // 1 byte   - listed multisig owners (0 = single-owner root) [v7]
+    pub threshold: u8,           // 1 byte   - owner signatures needed for owner-gated instructions [v7]
+    pub created_slot: u64,       // 8 bytes  - slot the root was created in, for exact ordering [v8]
+    pub open_contexts: u32,      // 4 bytes  - context and sub-context accounts not yet closed [v9]
+}
+
+impl RootIdentity {
+    pub const SIZE: usize = 8 + 1 + 32 + 32 + 33 + 8 + 1 + 2 + 2 + 4 + 33 + 32 + 8 + 8 + 8 + 1 + 96 + 1 + 33 + 8 + 1 + 1 + 2 + 48 + 32 + 2 + 8 + 8 + 8 + 8 + 160 + 1 + 1 + 8 + 4; // 643 bytes
+
+    /// Whether `amount` fits under the root-wide spend limit (always true when unlimited)
+    pub fn global_allows(&self, amount: u64) -> bool {
+        self.global_spend_limit == 0
+            || self.global_spent
+                .checked_add(amount)
+                .is_some_and(|total| total <= self.global_spend_limit)
+    }
+
+    /// Whether `key` may act as owner: the owner of a single-owner root, or any
+    /// listed owner of a multisig root
+    pub fn is_owner(&self, key: &Pubkey) -> bool {
+        if self.owner_count == 0 {
+            return self.owner == *key;
+        }
+        self.owners[..self.owner_count as usize].contains(key)
+    }
+
+    /// Drop multisig mode, e.g. once ownership moves to a single new wallet
+    pub fn clear_multisig(&mut self) {
+        self.owners = [Pubkey::default(); MAX_OWNERS];
+        self.owner_count = 0;
+        self.threshold = 0;
+    }
+
+    /// Whether a new context created at `now` would come too soon after the last one
+    pub fn creation_cooldown_active(&self, now: i64) -> bool {
+        self.creation_cooldown > 0
+            && now.saturating_sub(self.last_context_created_at) < self.creation_cooldown
+    }
+
+    /// Delay applied to privacy downgrades (DEFAULT_PRIVACY_DOWNGRADE_TIMELOCK when unset)
+    pub fn privacy_timelock(&self) -> i64 {
+        if self.privacy_downgrade_timelock == 0 {
+            DEFAULT_PRIVACY_DOWNGRADE_TIMELOCK
+        } else {
+            self.privacy_downgrade_timelock
+        }
+    }
+
+    /// Slot of `key` in the guardian list, if it is a guardian
+    pub fn guardian_index(&self, key: &Pubkey) -> Option<usize> {
+        if *key == Pubkey::default() {
+            return None;
+        }
+        self.guardians.iter().position(|guardian| guardian == key)
+    }
+
+    /// Per-transaction limit for a new context, substituting the type default for USE_TYPE_DEFAULT
+    /// `context_type` must already be validated (<= 5)
+    pub fn resolve_limit(&self, context_type: u8, max_per_transaction: u64) -> u64 {
+        if max_per_transaction == USE_TYPE_DEFAULT {
+            self.type_defaults[context_type as usize]
+        } else {
+            max_per_transaction
+        }
+    }
+}
+
+#[account]
+#[derive(Default)]
+pub struct ContextIdentity {
+    pub version: u8,                     // 1 byte   - layout version (CONTEXT_IDENTITY_VERSION)
+    pub root_identity: Pubkey,           // 32 bytes - parent root identity
+    pub root_identity_hash: Option<[u8; 32]>, // 33 bytes - optional hash of root identity for privacy
+    pub encryption_commitment: Option<[u8; 32]>, // 33 bytes - optional Arcium commitment for verification
+    pub encryption_epoch: u32,           // 4 bytes  - root's encryption epoch at creation (encrypted only)
+    pub computation_id: Option<[u8; 32]>, // 33 bytes - optional Arcium computation that produced the encryption
+    pub context_type: u8,                // 1 byte   - 0=DeFi, 1=Social, 2=Gaming, 3=Professional, 4=Temporary, 5=Public
+    pub created_at: i64,                 // 8 bytes  - unix timestamp
+    pub expires_at: Option<i64>,         // 9 bytes  - optional unix timestamp after which the context is unusable
+    pub mint: Option<Pubkey>,            // 33 bytes - SPL mint the limits are denominated in (None = lamports)
+    pub allowed_program: Option<Pubkey>, // 33 bytes - only program spends may target (None = any)
+    pub max_per_transaction: u64,        // 8 bytes  - spending limit per tx (lamports)
+    pub total_spent: u64,                 // 8 bytes  - total spent through this context
+    pub window_duration: i64,             // 8 bytes  - rolling window length in seconds (0 = no window limit)
+    pub window_start: i64,                // 8 bytes  - unix timestamp the current window started at
+    pub window_spent: u64,                // 8 bytes  - spent in the current window
+    pub max_per_window: u64,              // 8 bytes  - spending limit per window
+    pub revoked: bool,                    // 1 byte   - whether context is burned
+    pub revoked_at: i64,                  // 8 bytes  - unix timestamp of the last revocation (0 = never)
+    pub config_sealed: bool,              // 1 byte   - whether limits/settings are frozen forever
+    pub one_time_override: u64,           // 8 bytes  - single-use limit above max_per_transaction (0 = none)
+    pub override_expires_at: i64,         // 8 bytes  - unix timestamp the override lapses at
+    pub reserved_space: u16,              // 2 bytes  - extra bytes allocated beyond SIZE at creation
+    pub spend_multiple: u64,              // 8 bytes  - spends must be a multiple of this (0 = off)
+    pub spend_auth_code_hash: Option<[u8; 32]>, // 33 bytes - hash of the current single-use auth code
+    pub auth_code_expires_at: i64,        // 8 bytes  - unix timestamp the auth code lapses at
+    pub auth_code_threshold: u64,         // 8 bytes  - spends above this need an auth code (0 = off)
+    pub signature_binding: Option<[u8; 32]>, // 33 bytes - hash(context || message hash) signed by owner
+    pub event_digest: [u8; 32],           // 32 bytes - running hash chain over events this context emitted
+    pub event_tag: Option<u32>,           // 5 bytes  - optional tag echoed in every event for this context
+    pub context_index: u16,              // 2 bytes  - index for PDA derivation
+    pub bump: u8,                        // 1 byte   - PDA bump seed
+    // Fields below were appended after versioning; see CONTEXT_IDENTITY_VERSION
+    pub last_nonce: u64,                  // 8 bytes  - highest spend nonce used (0 = none) [v2]
+    pub commitment_updated_at: i64,       // 8 bytes  - unix timestamp of the last commitment rotation (0 = never) [v3]
+    pub parent_context: Option<Pubkey>,   // 33 bytes - context this sub-context was spawned from (None = top level) [v4]
+    pub child_count: u16,                 // 2 bytes  - sub-contexts spawned so far, next child index [v4]
+    pub verifier_pubkey: Option<Pubkey>,  // 33 bytes - key that signs commitment proofs for verify_commitment [v5]
+    pub label: [u8; 32],                  // 32 bytes - zero-padded UTF-8 client label (all zero = none) [v6]
+    pub epoch_spent: u64,                 // 8 bytes  - spent during last_epoch [v7]
+    pub last_epoch: u64,                  // 8 bytes  - Solana epoch epoch_spent belongs to [v7]
+    pub max_per_epoch: u64,               // 8 bytes  - spending limit per Solana epoch (0 = off) [v7]
+    pub delegate: Option<Pubkey>,         // 33 bytes - session key allowed to spend within limits [v8]
+    pub linkability_score: u64,           // 8 bytes  - spend exposure as of score_updated_at, decays over time [v9]
+    pub score_updated_at: i64,            // 8 bytes  - unix timestamp linkability_score was last updated [v9]
+    pub min_per_transaction: u64,         // 8 bytes  - smallest spend accepted, against dust probing (0 = off) [v10]
+    pub spend_count: u32,                 // 4 bytes  - number of spends recorded [v11]
+    pub max_spend_count: u32,             // 4 bytes  - spends allowed over the context's life (0 = unlimited) [v11]
+    pub pda_seed: Option<[u8; 32]>,       // 33 bytes - PDA seed used instead of the root key (encrypted contexts) [v12]
+    pub burn_proof: Option<[u8; 32]>,     // 33 bytes - hash(context || total_spent || revoked_at), set while revoked [v13]
+    pub created_slot: u64,                // 8 bytes  - slot the context was created in, for exact ordering [v14]
+    pub live_children: u16,               // 2 bytes  - sub-contexts neither revoked nor closed [v15]
+}
+
+impl ContextIdentity {
+    // Updated size: discriminator (8) + version (1) + root_identity (32) + root_identity_hash (1 + 32) + 
+    // encryption_commitment (1 + 32) + encryption_epoch (4) + computation_id (1 + 32) + context_type (1) +
+    // created_at (8) + expires_at (1 + 8) + mint (1 + 32) + allowed_program (1 + 32) +
+    // max_per_transaction (8) + total_spent (8) + window_duration (8) + window_start (8) + window_spent (8) +
+    // max_per_window (8) + revoked (1) + revoked_at (8) + config_sealed (1) + one_time_override (8) +
+    // override_expires_at (8) + reserved_space (2) + spend_multiple (8) + spend_auth_code_hash (1 + 32) +
+    // auth_code_expires_at (8) + auth_code_threshold (8) + signature_binding (1 + 32) +
+    // event_digest (32) + event_tag (1 + 4) + context_index (2) + bump (1) + last_nonce (8) +
+    // commitment_updated_at (8) + parent_context (1 + 32) + child_count (2) + verifier_pubkey (1 + 32) +
+    // label (32) + epoch_spent (8) + last_epoch (8) + max_per_epoch (8) + delegate (1 + 32) +
+    // linkability_score (8) + score_updated_at (8) + min_per_transaction (8) + spend_count (4) +
+    // max_spend_count (4) + pda_seed (1 + 32) + burn_proof (1 + 32) + created_slot (8) + live_children (2)
+    pub const SIZE: usize = 8 + 1 + 32 + 33 + 33 + 4 + 33 + 1 + 8 + 9 + 33 + 33 + 8 + 8 + 8 + 8 + 8 + 8 + 1 + 8 + 1 + 8 + 8 + 2 + 8 + 33 + 8 + 8 + 33 + 32 + 5 + 2 + 1 + 8 + 8 + 33 + 2 + 33 + 32 + 8 + 8 + 8 + 33 + 8 + 8 + 8 + 4 + 4 + 33 + 33 + 8 + 2; // 715 bytes
+
+    /// Whether this context's PDA is derived from its pda_seed under `root_key`
+    /// The pda_seed doesn't involve the root, so it only counts when the stored root
+    /// hash matches `root_key`; a context from another root then fails the seeds check.
+    fn uses_pda_seed(&self, root_key: &Pubkey) -> bool {
+        matches!(
+            (self.pda_seed, self.root_identity_hash),
+            (Some(_), Some(root_hash)) if root_hash_matches(root_key, &root_hash)
+        )
+    }
+
+    /// First PDA seed: ENCRYPTED_CONTEXT_SEED for pda_seed-derived contexts, so they
+    /// can never land on a plaintext context address, otherwise b"context"
+    pub fn seed_prefix(&self, root_key: &Pubkey) -> &'static [u8] {
+        if self.uses_pda_seed(root_key) {
+            ENCRYPTED_CONTEXT_SEED
+        } else {
+            b"context"
+        }
+    }
+
+    /// First seed of this context's ContextArchive: ENCRYPTED_ARCHIVE_SEED when the
+    /// context lives at a pda_seed address, otherwise b"archive"
+    pub fn archive_prefix(&self, root_key: &Pubkey) -> &'static [u8] {
+        if self.uses_pda_seed(root_key) {
+            ENCRYPTED_ARCHIVE_SEED
+        } else {
+            b"archive"
+        }
+    }
+
+    /// Seed following seed_prefix in this context's PDA: its pda_seed when set
+    /// (encrypted contexts), otherwise the root key
+    pub fn seed_key(&self, root_key: &Pubkey) -> [u8; 32] {
+        match self.pda_seed {
+            Some(seed) if self.uses_pda_seed(root_key) => seed,
+            _ => root_key.to_bytes(),
+        }
+    }
+
+    /// Allowance left in the rolling window containing `now` (None when there is no window)
+    pub fn window_remaining(&self, now: i64) -> Option<u64> {
+        if self.window_duration == 0 {
+            return None;
+        }
+        Some(self.max_per_window.saturating_sub(self.window_spent_at(now)))
+    }
+
+    /// Spend counted in the rolling window containing `now` (0 once the window has lapsed)
+    pub fn window_spent_at(&self, now: i64) -> u64 {
+        if now.saturating_sub(self.window_start) >= self.window_duration {
+            0
+        } else {
+            self.window_spent
+        }
+    }
+
+    /// Whether `amount` fits in the rolling window containing `now` (always true with no window)
+    pub fn window_allows(&self, amount: u64, now: i64) -> bool {
+        !matches!(self.window_remaining(now), Some(remaining) if amount > remaining)
+    }
+
+    /// Count `amount` against the rolling window, restarting it once window_duration
+    /// has elapsed (no-op without a window)
+    pub fn consume_window(&mut self, amount: u64, now: i64) -> Result<()> {
+        if self.window_duration > 0 {
+            if now.saturating_sub(self.window_start) >= self.window_duration {
+                self.window_spent = 0;
+                self.window_start = now;
+            }
+            let window_spent = self.window_spent.checked_add(amount)
+                .ok_or(PrismError::SpendingOverflow)?;
+            require!(window_spent <= self.max_per_window, PrismError::ExceedsWindowLimit);
+            self.window_spent = window_spent;
+        }
+        Ok(())
+    }
+
+    /// Count `amount` against the epoch limit, resetting it whenever the Solana epoch
+    /// changes (no-op without an epoch limit)
+    pub fn consume_epoch(&mut self, amount: u64, epoch: u64) -> Result<()> {
+        if self.max_per_epoch > 0 {
+            if epoch != self.last_epoch {
+                self.epoch_spent = 0;
+                self.last_epoch = epoch;
+            }
+            let epoch_spent = self.epoch_spent.checked_add(amount)
+                .ok_or(PrismError::SpendingOverflow)?;
+            require!(epoch_spent <= self.max_per_epoch, PrismError::ExceedsEpochLimit);
+            self.epoch_spent = epoch_spent;
+        }
+        Ok(())
+    }
+
+    /// Allowance left in Solana epoch `epoch` (None when there is no epoch limit)
+    pub fn epoch_remaining(&self, epoch: u64) -> Option<u64> {
+        if self.max_per_epoch == 0 {
+            return None;
+        }
+        Some(self.max_per_epoch.saturating_sub(self.epoch_spent_at(epoch)))
+    }
+
+    /// Spend counted in Solana epoch `epoch` (0 once the epoch has changed)
+    pub fn epoch_spent_at(&self, epoch: u64) -> u64 {
+        if epoch == self.last_epoch { self.epoch_spent } else { 0 }
+    }
+
+    /// Linkability score at `now`, after decaying since the last update
+    pub fn linkability_at(&self, now: i64) -> u64 {
+        let elapsed = now.saturating_sub(self.score_updated_at).max(0) as u64;
+        self.linkability_score
+            .saturating_sub(elapsed.saturating_mul(LINKABILITY_DECAY_PER_SECOND))
+    }
+
+    /// Whether `amount` fits in Solana epoch `epoch` (always true with no epoch limit)
+    pub fn epoch_allows(&self, amount: u64, epoch: u64) -> bool {
+        !matches!(self.epoch_remaining(epoch), Some(remaining) if amount > remaining)
+    }
+
+    /// Whether spends may target `program` (contexts without an allowlist accept any)
+    pub fn allows_program(&self, program: &Pubkey) -> bool {
+        match self.allowed_program {
+            Some(allowed) => allowed == *program,
+            None => true,
+        }
+    }
+
+    /// Whether the context has passed its expiry (contexts without one never expire)
+    pub fn is_expired(&self, now: i64) -> bool {
+        self.expires_at.is_some_and(|expires_at| now >= expires_at)
+    }
+
+    /// Whether `amount` satisfies the context's lot size (always true when unset)
+    // u64::is_multiple_of is newer than the Solana platform-tools rustc
+    #[allow(clippy::manual_is_multiple_of)]
+    pub fn is_aligned(&self, amount: u64) -> bool {
+        self.spend_multiple == 0 || amount % self.spend_multiple == 0
+    }
+
+    /// Whether an unexpired one-time override allows spending `amount`
+    pub fn override_covers(&self, amount: u64, now: i64) -> bool {
+        self.one_time_override > 0
+            && now < self.override_expires_at
+            && amount <= self.one_time_override
+    }
+
+    /// Bitmask of VIOLATION_* invariants this context's state breaks
+    pub fn state_violations(&self) -> u32 {
+        let mut violations = 0;
+        
+        let encrypted = self.root_identity_hash.is_some();
+        let encryption_fields_ok = if encrypted {
+            self.root_identity == Pubkey::default() && self.encryption_commitment.is_some()
+        } else {
+            self.root_identity != Pubkey::default() && self.encryption_commitment.is_none()
+        };
+        if !encryption_fields_ok {
+            violations |= VIOLATION_ENCRYPTION_FIELDS;
+        }
+        if !encrypted && (self.encryption_epoch != 0 || self.computation_id.is_some()) {
+            violations |= VIOLATION_PLAINTEXT_ENCRYPTION_DATA;
+        }
+        if self.one_time_override > 0 && self.override_expires_at == 0 {
+            violations |= VIOLATION_OVERRIDE;
+        }
+        if self.spend_auth_code_hash.is_none() != (self.auth_code_expires_at == 0) {
+            violations |= VIOLATION_AUTH_CODE;
+        }
+        if self.event_tag == Some(0) {
+            violations |= VIOLATION_EVENT_TAG;
+        }
+        if self.reserved_space > MAX_RESERVED_SPACE {
+            violations |= VIOLATION_RESERVED_SPACE;
+        }
+        
+        violations
+    }
+
+    /// Fold raw event data into the running event digest
+    pub fn fold_event_digest(&mut self, event_data: &[u8]) {
+        self.event_digest = chain_event_hash(&self.event_digest, &hash(event_data).to_bytes());
+    }
+}
+
+#[account]
+pub struct ProgramConfig {
+    pub admin: Pubkey,           // 32 bytes - authority allowed to pause the program
+    pub paused: bool,            // 1 byte   - whether mutating instructions are halted
+    pub bump: u8,                // 1 byte   - PDA bump seed
+    pub verbose_events: bool,    // 1 byte   - whether optional events such as SpendHook are emitted
+}
+
+impl ProgramConfig {
+    pub const SIZE: usize = 8 + 32 + 1 + 1 + 1; // 43 bytes
+}
+
+#[account]
+pub struct ContextArchive {
+    pub context_identity: Pubkey,        // 32 bytes - the closed context's address
+    pub context_type: u8,                // 1 byte   - context type at close
+    pub total_spent: u64,                // 8 bytes  - final total spent
+    pub created_at: i64,                 // 8 bytes  - when the context was created
+    pub closed_at: i64,                  // 8 bytes  - when the context was revoked and closed
+    pub context_index: u16,              // 2 bytes  - index the context was derived with
+    pub bump: u8,                        // 1 byte   - PDA bump seed
+}
+
+impl ContextArchive {
+    pub const SIZE: usize = 8 + 32 + 1 + 8 + 8 + 8 + 2 + 1; // 68 bytes
+}
+
+/// RootIdentity as created before layout versions existed; only read by migrate_account
+#[derive(AnchorSerialize, AnchorDeserialize)]
+pub struct LegacyRootIdentity {
+    pub owner: Pubkey,           // 32 bytes - wallet that owns this identity
+    pub created_at: i64,         // 8 bytes  - unix timestamp
+    pub privacy_level: u8,       // 1 byte   - 0=Maximum, 1=High, 2=Medium, 3=Low, 4=Public
+    pub context_count: u16,      // 2 bytes  - number of contexts created
+    pub bump: u8,                // 1 byte   - PDA bump seed
+}
+
+impl LegacyRootIdentity {
+    pub const SIZE: usize = 8 + 32 + 8 + 1 + 2 + 1; // 52 bytes
+
+    /// The same root in the current layout; the PDA was seeded by the owner, who is
+    /// therefore also the creator, and legacy roots had no context cap
+    pub fn upgrade(self) -> RootIdentity {
+        RootIdentity {
+            owner: self.owner,
+            creator: self.owner,
+            created_at: self.created_at,
+            privacy_level: self.privacy_level,
+            context_count: self.context_count,
+            max_contexts: u16::MAX,
+            bump: self.bump,
+            ..Default::default()
+        }
+    }
+}
+
+/// ContextIdentity as created before layout versions existed; only read by migrate_account
+#[derive(AnchorSerialize, AnchorDeserialize)]
+pub struct LegacyContextIdentity {
+    pub root_identity: Pubkey,           // 32 bytes - parent root identity
+    pub root_identity_hash: Option<[u8; 32]>, // 33 bytes - optional hash of root identity for privacy
+    pub encryption_commitment: Option<[u8; 32]>, // 33 bytes - optional Arcium commitment for verification
+    pub context_type: u8,                // 1 byte   - 0=DeFi, 1=Social, 2=Gaming, 3=Professional, 4=Temporary, 5=Public
+    pub created_at: i64,                 // 8 bytes  - unix timestamp
+    pub max_per_transaction: u64,        // 8 bytes  - spending limit per tx (lamports)
+    pub total_spent: u64,                // 8 bytes  - total spent through this context
+    pub revoked: bool,                   // 1 byte   - whether context is burned
+    pub context_index: u16,              // 2 bytes  - index for PDA derivation
+    pub bump: u8,                        // 1 byte   - PDA bump seed
+}
+
+impl LegacyContextIdentity {
+    pub const SIZE: usize = 8 + 32 + 33 + 33 + 1 + 8 + 8 + 8 + 1 + 2 + 1; // 135 bytes
+
+    /// The same context in the current layout; every later field takes its default
+    pub fn upgrade(self) -> ContextIdentity {
+        ContextIdentity {
+            root_identity: self.root_identity,
+            root_identity_hash: self.root_identity_hash,
+            encryption_commitment: self.encryption_commitment,
+            context_type: self.context_type,
+            created_at: self.created_at,
+            max_per_transaction: self.max_per_transaction,
+            total_spent: self.total_spent,
+            revoked: self.revoked,
+            context_index: self.context_index,
+            bump: self.bump,
+            ..Default::default()
+        }
+    }
+}
+
+// ============================================================================
+// RETURN TYPES
+// ============================================================================
+
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, PartialEq, Eq)]
+pub struct CommitmentVerification {
+    pub is_valid: bool,
+    pub binding_key: Pubkey,
+}
+
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, PartialEq, Eq)]
+pub struct CreatedContext {
+    pub context_identity: Pubkey,
+    pub context_index: u16,
+}
+
+#[derive(AnchorSerialize, AnchorDeserialize, Clone)]
+pub struct ProgramInfo {
+    pub program_id: Pubkey,
+    pub version: u16,
+    pub root_identity_version: u8,
+    pub context_identity_version: u8,
+}
+
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, PartialEq, Eq)]
+pub struct Timelock {
+    pub expires_at: i64,        // 0 when inactive
+    pub remaining_seconds: i64, // 0 when inactive
+}
+
+impl Timelock {
+    pub const INACTIVE: Timelock = Timelock { expires_at: 0, remaining_seconds: 0 };
+
+    /// A timelock expiring at `expires_at`, or INACTIVE if it has already lapsed
+    pub fn until(expires_at: i64, now: i64) -> Timelock {
+        if expires_at > now {
+            Timelock { expires_at, remaining_seconds: expires_at - now }
+        } else {
+            Timelock::INACTIVE
+        }
+    }
+}
+
+#[derive(AnchorSerialize, AnchorDeserialize, Clone)]
+pub struct ContextStatus {
+    pub revoked: bool,
+    pub expired: bool,
+    pub total_spent: u64,
+    pub max_per_transaction: u64,
+    pub remaining_window: Option<u64>, // None when no window limit is set
+    pub expires_at: Option<i64>,
+}
+
+#[derive(AnchorSerialize, AnchorDeserialize, Clone)]
+pub struct ActiveTimelocks {
+    pub expiry: Timelock,
+    pub one_time_override: Timelock,
+    pub auth_code: Timelock,
+    pub reactivation_cooldown: Timelock, // until a revoked context may be reactivated
+    pub privacy_downgrade: Timelock,     // until a pending privacy downgrade can be finalized
+    pub recovery: Timelock,              // until a pending guardian recovery can be executed
+}
+
+// ============================================================================
+// INSTRUCTION ARGUMENTS
+// ============================================================================
+
+#[derive(AnchorSerialize, AnchorDeserialize, Clone)]
+pub struct ContextSpec {
+    pub context_type: u8,
+    pub max_per_transaction: u64,
+}
+
+// ============================================================================
+// CONTEXT TYPES (for reference)
+// ============================================================================
+
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, PartialEq, Eq)]
+pub enum ContextType {
+    DeFi = 0,        // Dark pool trading, swaps
+    Social = 1,      // Social interactions
+    Gaming = 2,      // Gaming activities  
+    Professional = 3, // Work-related
+    Temporary = 4,   // Auto-burn after use
+    Public = 5,      // Flex mode - fully public
+}
+
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, PartialEq, Eq)]
+pub enum PrivacyLevel {
+    Maximum = 0,     // Full anonymity
+    High = 1,        // Minimal disclosure
+    Medium = 2,      // Balanced
+    Low = 3,         // More transparent
+    Public = 4,      // Fully public
+}
+
+// ============================================================================
+// EVENTS
+// ============================================================================
+
+#[event]
+pub struct RootIdentityCreated {
+    pub owner: Pubkey,
+    pub privacy_level: u8,
+    pub created_slot: u64,
+    pub timestamp: i64,
+}
+
+#[event]
+pub struct ContextCreated {
+    pub root_identity: Pubkey,
+    pub context_identity: Pubkey,
+    pub context_type: u8,
+    pub max_per_transaction: u64,
+    pub context_index: u16,
+    pub expires_at: Option<i64>,
+    pub label: [u8; 32],
+    pub event_tag: Option<u32>,
+    pub created_slot: u64,
+    pub timestamp: i64,
+}
+
+#[event]
+pub struct EncryptedContextCreated {
+    pub context_identity: Pubkey,
+    pub root_identity_hash: [u8; 32],
+    pub context_type: u8,
+    pub computation_id: Option<[u8; 32]>,
+    pub label: [u8; 32],
+    pub event_tag: Option<u32>,
+    pub created_slot: u64,
+    pub timestamp: i64,
+}
+
+#[event]
+pub struct ContextRevoked {
+    pub root_identity: Pubkey,
+    pub context_identity: Pubkey,
+    pub context_type: u8,
+    pub total_spent: u64,
+    pub context_count: u16,
+    pub revoked_count: u16,
+    pub event_tag: Option<u32>,
+    pub burn_proof: [u8; 32],
+    pub timestamp: i64,
+}
+
+#[event]
+pub struct SpendingRecorded {
+    pub context_identity: Pubkey,
+    pub amount: u64,
+    pub total_spent: u64,
+    pub spend_count: u32,
+    pub override_consumed: bool,
+    pub expected_amount: Option<u64>,
+    pub mint: Option<Pubkey>,
+    pub event_tag: Option<u32>,
+    pub timestamp: i64,
+}
+
+#[event]
+pub struct PrivacyLevelUpdated {
+    pub root_identity: Pubkey,
+    pub old_level: u8,
+    pub new_level: u8,
+    pub timestamp: i64,
+}
+
+#[event]
+pub struct ContextSealed {
+    pub context_identity: Pubkey,
+    pub event_tag: Option<u32>,
+    pub timestamp: i64,
+}
+
+#[event]
+pub struct EncryptionEpochRotated {
+    pub root_identity: Pubkey,
+    pub old_epoch: u32,
+    pub new_epoch: u32,
+    pub timestamp: i64,
+}
+
+#[event]
+pub struct PrivacyPolicySet {
+    pub root_identity: Pubkey,
+    pub policy_hash: [u8; 32],
+    pub timestamp: i64,
+}
+
+#[event]
+pub struct OverrideAuthorized {
+    pub context_identity: Pubkey,
+    pub amount: u64,
+    pub expires_at: i64,
+    pub event_tag: Option<u32>,
+    pub timestamp: i64,
+}
+
+#[event]
+pub struct SpendingAttested {
+    pub context_identity: Pubkey,
+    pub threshold: u64,
+    pub below: bool,
+    pub event_tag: Option<u32>,
+    pub timestamp: i64,
+}
+
+#[event]
+pub struct ContextsMigratedToEpoch {
+    pub root_identity: Pubkey,
+    pub epoch: u32,
+    pub count: u8,
+    pub timestamp: i64,
+}
+
+#[event]
+pub struct SpendAuthCodeSet {
+    pub context_identity: Pubkey,
+    pub threshold: u64,
+    pub expires_at: i64,
+    pub event_tag: Option<u32>,
+    pub timestamp: i64,
+}
+
+#[event]
+pub struct AuthCodeConsumed {
+    pub context_identity: Pubkey,
+    pub amount: u64,
+    pub event_tag: Option<u32>,
+    pub timestamp: i64,
+}
+
+#[event]
+pub struct CounterpartyPaymentSwept {
+    pub context_identity: Pubkey,
+    pub amount: u64,
+    pub event_tag: Option<u32>,
+    pub timestamp: i64,
+}
+
+#[event]
+pub struct RingMembershipProved {
+    pub context_identity: Pubkey,
+    pub ring_hash: [u8; 32],
+    pub ring_size: u8,
+    pub is_member: bool,
+    pub event_tag: Option<u32>,
+    pub timestamp: i64,
+}
+
+#[event]
+pub struct SpendHook {
+    pub context_identity: Pubkey,
+    pub amount: u64,
+    pub hook_data: [u8; 32],
+    pub event_tag: Option<u32>,
+    pub timestamp: i64,
+}
+
+#[event]
+pub struct ContextClosed {
+    pub context_identity: Pubkey,
+    pub rent_reclaimed: u64,
+    pub event_tag: Option<u32>,
+    pub timestamp: i64,
+}
+
+#[event]
+pub struct SignatureBound {
+    pub context_identity: Pubkey,
+    pub binding_commitment: [u8; 32],
+    pub event_tag: Option<u32>,
+    pub timestamp: i64,
+}
+
+#[event]
+pub struct SpendMultipleSet {
+    pub context_identity: Pubkey,
+    pub spend_multiple: u64,
+    pub event_tag: Option<u32>,
+    pub timestamp: i64,
+}
+
+#[event]
+pub struct ActiveAttestation {
+    pub context_identity: Pubkey,
+    pub slot: u64,
+    pub nonce: u64,
+    pub event_tag: Option<u32>,
+    pub timestamp: i64,
+}
+
+#[event]
+pub struct EmergencyContactSet {
+    pub root_identity: Pubkey,
+    pub emergency_contact_hash: [u8; 32],
+    pub timestamp: i64,
+}
+
+#[event]
+pub struct WindowLimitSet {
+    pub context_identity: Pubkey,
+    pub window_duration: i64,
+    pub max_per_window: u64,
+    pub event_tag: Option<u32>,
+    pub timestamp: i64,
+}
+
+#[event]
+pub struct ReactivationCooldownSet {
+    pub root_identity: Pubkey,
+    pub reactivation_cooldown: i64,
+    pub timestamp: i64,
+}
+
+#[event]
+pub struct ContextReactivated {
+    pub context_identity: Pubkey,
+    pub context_count: u16,
+    pub revoked_count: u16,
+    pub event_tag: Option<u32>,
+    pub timestamp: i64,
+}
+
+#[event]
+pub struct OwnershipProposed {
+    pub root_identity: Pubkey,
+    pub owner: Pubkey,
+    pub pending_owner: Pubkey,
+    pub timestamp: i64,
+}
+
+#[event]
+pub struct OwnershipAccepted {
+    pub root_identity: Pubkey,
+    pub old_owner: Pubkey,
+    pub new_owner: Pubkey,
+    pub timestamp: i64,
+}
+
+#[event]
+pub struct OwnershipTransferCancelled {
+    pub root_identity: Pubkey,
+    pub owner: Pubkey,
+    pub pending_owner: Pubkey,
+    pub timestamp: i64,
+}
+
+#[event]
+pub struct GlobalSpendLimitSet {
+    pub root_identity: Pubkey,
+    pub global_spend_limit: u64,
+    pub global_spent: u64,
+    pub timestamp: i64,
+}
+
+#[event]
+pub struct ContextBatchCreated {
+    pub root_identity: Pubkey,
+    pub first_index: u16,
+    pub count: u8,
+    pub created_slot: u64,
+    pub timestamp: i64,
+}
+
+#[event]
+pub struct ContextsRevoked {
+    pub root_identity: Pubkey,
+    pub count: u8,
+    pub context_count: u16,
+    pub revoked_count: u16,
+    pub timestamp: i64,
+}
+
+#[event]
+pub struct RootFrozen {
+    pub root_identity: Pubkey,
+    pub timestamp: i64,
+}
+
+#[event]
+pub struct RootUnfrozen {
+    pub root_identity: Pubkey,
+    pub timestamp: i64,
+}
+
+#[event]
+pub struct EmergencyAction {
+    pub root_identity: Pubkey,
+    pub action: u8,
+    pub emergency_contact_hash: [u8; 32],
+    pub timestamp: i64,
+}
 
-#[derive(Accounts)]
-pub struct RecordSpending<'info> {
-    #[account(mut)]
-    pub user: Signer<'info>,
-    
-    #[account(
-        seeds = [b"root", user.key().as_ref()],
-        bump = root_identity.bump,
-        constraint = root_identity.owner == user.key() @ PrismError::Unauthorized
-    )]
-    pub root_identity: Account<'info, RootIdentity>,
-    
-    #[account(
-        mut,
-        seeds = [
-            b"context",
-            root_identity.key().as_ref(),
-            &context_identity.context_index.to_le_bytes()
-        ],
-        bump = context_identity.bump
-    )]
-    pub context_identity: Account<'info, ContextIdentity>,
+#[event]
+pub struct GuardiansSet {
+    pub root_identity: Pubkey,
+    pub guardians: [Pubkey; 3],
+    pub recovery_threshold: u8,
+    pub timestamp: i64,
 }
 
-#[derive(Accounts)]
-pub struct UpdatePrivacyLevel<'info> {
-    #[account(mut)]
-    pub user: Signer<'info>,
-    
-    #[account(
-        mut,
-        seeds = [b"root", user.key().as_ref()],
-        bump = root_identity.bump,
-        constraint = root_identity.owner == user.key() @ PrismError::Unauthorized
-    )]
-    pub root_identity: Account<'info, RootIdentity>,
+#[event]
+pub struct RecoveryInitiated {
+    pub root_identity: Pubkey,
+    pub guardian: Pubkey,
+    pub new_owner: Pubkey,
+    pub timestamp: i64,
+}
+
+#[event]
+pub struct RecoveryApproved {
+    pub root_identity: Pubkey,
+    pub guardian: Pubkey,
+    pub new_owner: Pubkey,
+    pub approvals: u8,
+    pub timestamp: i64,
+}
+
+#[event]
+pub struct RecoveryCancelled {
+    pub root_identity: Pubkey,
+    pub new_owner: Pubkey,
+    pub timestamp: i64,
+}
+
+#[event]
+pub struct AccountMigrated {
+    pub account: Pubkey,
+    pub from_version: u8,
+    pub to_version: u8,
+    pub timestamp: i64,
+}
+
+#[event]
+pub struct ContextResized {
+    pub context_identity: Pubkey,
+    pub old_size: u32,
+    pub new_size: u32,
+    pub timestamp: i64,
+}
+
+#[event]
+pub struct CommitmentRotated {
+    pub context_identity: Pubkey,
+    pub old_commitment: [u8; 32],
+    pub new_commitment: [u8; 32],
+    pub event_tag: Option<u32>,
+    pub timestamp: i64,
+}
+
+#[event]
+pub struct TypeDefaultsSet {
+    pub root_identity: Pubkey,
+    pub type_defaults: [u64; 6],
+    pub timestamp: i64,
+}
+
+#[event]
+pub struct ConfigInitialized {
+    pub admin: Pubkey,
+    pub timestamp: i64,
+}
+
+#[event]
+pub struct ConfigAdminChanged {
+    pub previous_admin: Pubkey,
+    pub new_admin: Pubkey,
+    pub timestamp: i64,
+}
+
+#[event]
+pub struct VerboseEventsSet {
+    pub admin: Pubkey,
+    pub enabled: bool,
+    pub timestamp: i64,
+}
+
+#[event]
+pub struct ProgramPaused {
+    pub admin: Pubkey,
+    pub timestamp: i64,
+}
+
+#[event]
+pub struct ProgramUnpaused {
+    pub admin: Pubkey,
+    pub timestamp: i64,
+}
+
+#[event]
+pub struct SubContextCreated {
+    pub parent_context: Pubkey,
+    pub context_identity: Pubkey,
+    pub child_index: u16,
+    pub context_type: u8,
+    pub max_per_transaction: u64,
+    pub event_tag: Option<u32>,
+    pub created_slot: u64,
+    pub timestamp: i64,
+}
+
+#[event]
+pub struct SubContextsRevoked {
+    pub parent_context: Pubkey,
+    pub count: u8,
+    pub event_tag: Option<u32>,
+    pub timestamp: i64,
+}
+
+#[event]
+pub struct VerifierKeySet {
+    pub context_identity: Pubkey,
+    pub verifier_pubkey: Option<Pubkey>,
+    pub event_tag: Option<u32>,
+    pub timestamp: i64,
+}
+
+#[event]
+pub struct SpendingRefunded {
+    pub context_identity: Pubkey,
+    pub amount: u64,
+    pub total_spent: u64,
+    pub spend_count: u32,
+    pub mint: Option<Pubkey>,
+    pub event_tag: Option<u32>,
+    pub timestamp: i64,
+}
+
+#[event]
+pub struct ContextLabelSet {
+    pub context_identity: Pubkey,
+    pub label: [u8; 32],
+    pub event_tag: Option<u32>,
+    pub timestamp: i64,
+}
+
+#[event]
+pub struct RootIdentityClosed {
+    pub root_identity: Pubkey,
+    pub owner: Pubkey,
+    pub context_count: u16,
+    pub timestamp: i64,
+}
+
+#[event]
+pub struct EpochLimitSet {
+    pub context_identity: Pubkey,
+    pub max_per_epoch: u64,
+    pub epoch: u64,
+    pub event_tag: Option<u32>,
+    pub timestamp: i64,
+}
+
+#[event]
+pub struct ContextsMerkleRootUpdated {
+    pub root_identity: Pubkey,
+    pub old_root: [u8; 32],
+    pub new_root: [u8; 32],
+    pub timestamp: i64,
+}
+
+#[event]
+pub struct ContextDelegateSet {
+    pub context_identity: Pubkey,
+    pub old_delegate: Option<Pubkey>,
+    pub new_delegate: Option<Pubkey>,
+    pub event_tag: Option<u32>,
+    pub timestamp: i64,
+}
+
+#[event]
+pub struct PrivacyChangeProposed {
+    pub root_identity: Pubkey,
+    pub current_level: u8,
+    pub pending_level: u8,
+    pub eta: i64,
+    pub timestamp: i64,
+}
+
+#[event]
+pub struct PrivacyChangeCancelled {
+    pub root_identity: Pubkey,
+    pub pending_level: u8,
+    pub timestamp: i64,
+}
+
+#[event]
+pub struct PrivacyTimelockSet {
+    pub root_identity: Pubkey,
+    pub privacy_downgrade_timelock: i64,
+    pub timestamp: i64,
+}
+
+#[event]
+pub struct MaxSpendCountSet {
+    pub context_identity: Pubkey,
+    pub max_spend_count: u32,
+    pub spend_count: u32,
+    pub event_tag: Option<u32>,
+    pub timestamp: i64,
+}
+
+#[event]
+pub struct CreationCooldownSet {
+    pub root_identity: Pubkey,
+    pub creation_cooldown: i64,
+    pub timestamp: i64,
+}
+
+#[event]
+pub struct ContextLimitUpdated {
+    pub context_identity: Pubkey,
+    pub old_limit: u64,
+    pub new_limit: u64,
+    pub event_tag: Option<u32>,
+    pub timestamp: i64,
+}
+
+#[event]
+pub struct MultisigConfigured {
+    pub root_identity: Pubkey,
+    pub owners: [Pubkey; 5],
+    pub owner_count: u8,
+    pub threshold: u8,
+    pub timestamp: i64,
 }
 
 // ============================================================================
-// ACCOUNT STRUCTS
+// ERRORS
 // ============================================================================
 
-#[account]
-pub struct RootIdentity {
-    pub owner: Pubkey,           // 32 bytes - wallet that owns this identity
-    pub created_at: i64,         // 8 bytes  - unix timestamp
-    pub privacy_level: u8,       // 1 byte   - 0=Maximum, 1=High, 2=Medium, 3=Low, 4=Public
-    pub context_count: u16,      // 2 bytes  - number of contexts created
-    pub bump: u8,                // 1 byte   - PDA bump seed
+// Helper function to hash root identity (domain-separated, used for all new contexts)
+fn hash_root_identity(root_pubkey: &Pubkey) -> [u8; 32] {
+    hashv(&[ROOT_IDENTITY_HASH_DOMAIN, root_pubkey.as_ref()]).to_bytes()
 }
 
-impl RootIdentity {
-    pub const SIZE: usize = 8 + 32 + 8 + 1 + 2 + 1; // 52 bytes
+// Legacy root identity hash without a domain tag, kept so contexts created
+// before domain separation still verify
+fn hash_root_identity_v1(root_pubkey: &Pubkey) -> [u8; 32] {
+    let hash_result = hash(&root_pubkey.to_bytes());
+    hash_result.to_bytes()
 }
 
-#[account]
-pub struct ContextIdentity {
-    pub root_identity: Pubkey,           // 32 bytes - parent root identity
-    pub root_identity_hash: Option<[u8; 32]>, // 33 bytes - optional hash of root identity for privacy
-    pub encryption_commitment: Option<[u8; 32]>, // 33 bytes - optional Arcium commitment for verification
-    pub context_type: u8,                // 1 byte   - 0=DeFi, 1=Social, 2=Gaming, 3=Professional, 4=Temporary, 5=Public
-    pub created_at: i64,                 // 8 bytes  - unix timestamp
-    pub max_per_transaction: u64,        // 8 bytes  - spending limit per tx (lamports)
-    pub total_spent: u64,                 // 8 bytes  - total spent through this context
-    pub revoked: bool,                    // 1 byte   - whether context is burned
-    pub context_index: u16,              // 2 bytes  - index for PDA derivation
-    pub bump: u8,                        // 1 byte   - PDA bump seed
+// Helper to check a stored root identity hash against a root under either scheme
+fn root_hash_matches(root_pubkey: &Pubkey, root_hash: &[u8; 32]) -> bool {
+    hash_root_identity(root_pubkey) == *root_hash || hash_root_identity_v1(root_pubkey) == *root_hash
+}
+
+// Helper to extend an event digest: hash(digest || event_hash)
+fn chain_event_hash(digest: &[u8; 32], event_hash: &[u8; 32]) -> [u8; 32] {
+    let mut preimage = [0u8; 64];
+    preimage[..32].copy_from_slice(digest);
+    preimage[32..].copy_from_slice(event_hash);
+    hash(&preimage).to_bytes()
+}
+
+// Helper to compute the signature binding commitment for a context
+fn signature_binding_commitment(context_key: &Pubkey, message: &[u8]) -> [u8; 32] {
+    let message_hash = hash(message).to_bytes();
+    let mut preimage = Vec::with_capacity(64);
+    preimage.extend_from_slice(context_key.as_ref());
+    preimage.extend_from_slice(&message_hash);
+    hash(&preimage).to_bytes()
+}
+
+// Helper to compute the message a verifier signs to attest that `commitment`
+// belongs to the context and is bound to `binding_key`
+fn commitment_proof_message(context_key: &Pubkey, commitment: &[u8; 32], binding_key: &Pubkey) -> [u8; 32] {
+    hashv(&[b"prism:commitment-proof:v1", context_key.as_ref(), commitment, binding_key.as_ref()]).to_bytes()
+}
+
+// Helper to compute a revoked context's burn proof: hash(context || total_spent || revoked_at)
+fn burn_proof_hash(context_key: &Pubkey, total_spent: u64, revoked_at: i64) -> [u8; 32] {
+    hashv(&[context_key.as_ref(), &total_spent.to_le_bytes(), &revoked_at.to_le_bytes()]).to_bytes()
+}
+
+// Helper shared by the verify_commitment variants: the signed proof must come from
+// the context's verifier and bind the stored commitment to the context itself
+fn commitment_proof_valid(
+    context: &Account<ContextIdentity>,
+    commitment: &[u8; 32],
+    binding_key: &Pubkey,
+    signer: &Pubkey,
+    message: &[u8],
+) -> bool {
+    match (context.encryption_commitment, context.verifier_pubkey) {
+        (Some(stored_commitment), Some(verifier)) => {
+            stored_commitment == *commitment
+                && *binding_key == context.key()
+                && *signer == verifier
+                && message == commitment_proof_message(&context.key(), commitment, binding_key)
+        }
+        // Nothing to verify against without both a commitment and a verifier key
+        _ => false,
+    }
+}
+
+// Helper to read the signer and message of the Ed25519 program instruction
+// immediately preceding the current one. The runtime has already verified the
+// signature; we only accept a single signature whose data lives inline.
+fn load_ed25519_signature(instructions: &AccountInfo) -> Result<(Pubkey, Vec<u8>)> {
+    let current_index = load_current_index_checked(instructions)?;
+    require!(current_index > 0, PrismError::InvalidSignatureInstruction);
+    
+    let ix = load_instruction_at_checked((current_index - 1) as usize, instructions)?;
+    require!(
+        ix.program_id == ed25519_program::ID,
+        PrismError::InvalidSignatureInstruction
+    );
+    
+    // Layout: [num_signatures: u8, padding: u8, offsets: 7 x u16, ...data]
+    let data = &ix.data;
+    require!(data.len() >= 16 && data[0] == 1, PrismError::InvalidSignatureInstruction);
+    let read_u16 = |at: usize| u16::from_le_bytes([data[at], data[at + 1]]);
+    
+    let public_key_offset = read_u16(6) as usize;
+    let message_offset = read_u16(10) as usize;
+    let message_size = read_u16(12) as usize;
+    require!(
+        read_u16(4) == u16::MAX && read_u16(8) == u16::MAX && read_u16(14) == u16::MAX,
+        PrismError::InvalidSignatureInstruction
+    );
+    
+    let public_key = data
+        .get(public_key_offset..public_key_offset + 32)
+        .ok_or(PrismError::InvalidSignatureInstruction)?;
+    let message = data
+        .get(message_offset..message_offset + message_size)
+        .ok_or(PrismError::InvalidSignatureInstruction)?;
+    
+    Ok((Pubkey::try_from(public_key).unwrap(), message.to_vec()))
+}
+
+// Helper to check a label is UTF-8 followed only by zero padding
+fn is_valid_label(label: &[u8; 32]) -> bool {
+    let len = label.iter().position(|b| *b == 0).unwrap_or(label.len());
+    label[len..].iter().all(|b| *b == 0) && std::str::from_utf8(&label[..len]).is_ok()
+}
+
+// Helper shared by the check_spending_limit variants for the context's own limits
+fn check_context_spend(
+    context: &ContextIdentity,
+    amount: u64,
+    target_program: &Pubkey,
+    clock: &Clock,
+) -> Result<()> {
+    let now = clock.unix_timestamp;
+    require!(!context.revoked, PrismError::ContextRevoked);
+    require!(!context.is_expired(now), PrismError::ContextExpired);
+    require!(context.allows_program(target_program), PrismError::ProgramNotAllowed);
+    require!(context.is_aligned(amount), PrismError::AmountNotAligned);
+    require!(amount >= context.min_per_transaction, PrismError::BelowTransactionMinimum);
+    require!(
+        context.max_spend_count == 0 || context.spend_count < context.max_spend_count,
+        PrismError::SpendCountExceeded
+    );
+    require!(
+        amount <= context.max_per_transaction || context.override_covers(amount, now),
+        PrismError::ExceedsTransactionLimit
+    );
+    require!(context.window_allows(amount, now), PrismError::ExceedsWindowLimit);
+    require!(context.epoch_allows(amount, clock.epoch), PrismError::ExceedsEpochLimit);
+    Ok(())
+}
+
+// Helper to fold a context leaf up a Merkle path; sibling pairs are hashed in
+// ascending order so the path needs no direction bits
+fn merkle_root_from_proof(context: &Pubkey, proof: &[[u8; 32]]) -> [u8; 32] {
+    let leaf = hashv(&[CONTEXT_LEAF_DOMAIN, context.as_ref()]).to_bytes();
+    proof.iter().fold(leaf, |node, sibling| {
+        let (left, right) = if node <= *sibling { (&node, sibling) } else { (sibling, &node) };
+        hashv(&[CONTEXT_NODE_DOMAIN, left, right]).to_bytes()
+    })
+}
+
+// Helper to look up the creation policy matrix for a root's privacy level
+fn context_type_allowed(privacy_level: u8, context_type: u8, encrypted: bool) -> bool {
+    let policy = if encrypted { &ENCRYPTED_CONTEXT_POLICY } else { &PLAINTEXT_CONTEXT_POLICY };
+    policy
+        .get(privacy_level as usize)
+        .is_some_and(|allowed| allowed & (1 << context_type) != 0)
+}
+
+// What create_context_encrypted stores instead of the plaintext root key
+struct ContextEncryption {
+    root_identity_hash: [u8; 32],
+    commitment: [u8; 32],
+    computation_id: Option<[u8; 32]>,
+}
+
+// Helper shared by the context constructors: checks the root may take another
+// context, fills in the freshly allocated account and emits ContextCreated, or
+// EncryptedContextCreated when `encryption` is given
+#[allow(clippy::too_many_arguments)]
+fn init_context(
+    root: &mut Account<RootIdentity>,
+    context: &mut Account<ContextIdentity>,
+    bump: u8,
+    context_type: u8,
+    max_per_transaction: u64,
+    reserved_space: u16,
+    event_tag: Option<u32>,
+    expires_at: Option<i64>,
+    mint: Option<Pubkey>,
+    allowed_program: Option<Pubkey>,
+    label: [u8; 32],
+    min_per_transaction: u64,
+    encryption: Option<ContextEncryption>,
+) -> Result<CreatedContext> {
+    require!(context_type <= 5, PrismError::InvalidContextType);
+    require!(is_valid_label(&label), PrismError::InvalidLabel);
+    require!(reserved_space <= MAX_RESERVED_SPACE, PrismError::ReservedSpaceTooLarge);
+    
+    // An expiry in the past would only create a dead context
+    let clock = Clock::get()?;
+    let now = clock.unix_timestamp;
+    if let Some(expires_at) = expires_at {
+        require!(expires_at > now, PrismError::InvalidExpiry);
+    }
+    
+    require!(root.context_count < root.max_contexts, PrismError::MaxContextsReached);
+    require!(!root.creation_cooldown_active(now), PrismError::CreationCooldownActive);
+    require!(
+        context_type_allowed(root.privacy_level, context_type, encryption.is_some()),
+        PrismError::ContextTypeNotAllowedAtPrivacyLevel
+    );
+    let max_per_transaction = root.resolve_limit(context_type, max_per_transaction);
+    require!(
+        min_per_transaction <= max_per_transaction,
+        PrismError::InvalidTransactionMinimum
+    );
+    
+    context.version = CONTEXT_IDENTITY_VERSION;
+    match &encryption {
+        // Store ONLY the hashed root identity: a zero root_identity marks the context
+        // as encrypted and all verification goes through root_identity_hash
+        Some(encryption) => {
+            context.root_identity = Pubkey::default();
+            context.root_identity_hash = Some(encryption.root_identity_hash);
+            context.encryption_commitment = Some(encryption.commitment);
+            context.encryption_epoch = root.encryption_epoch; // Groups contexts created in the same epoch
+            context.computation_id = encryption.computation_id; // Arcium MPC job that produced the encryption
+            // Kept even if the commitment is later rotated, so the PDA stays derivable
+            context.pda_seed = Some(encryption.commitment);
+        }
+        None => {
+            context.root_identity = root.key();
+            context.root_identity_hash = None;
+            context.encryption_commitment = None;
+            context.encryption_epoch = 0;
+            context.computation_id = None;
+            context.pda_seed = None;
+        }
+    }
+    context.context_type = context_type;
+    context.created_at = now;
+    context.expires_at = expires_at;
+    context.mint = mint;
+    context.allowed_program = allowed_program;
+    context.max_per_transaction = max_per_transaction;
+    context.total_spent = 0;
+    context.window_duration = 0;
+    context.window_start = 0;
+    context.window_spent = 0;
+    context.max_per_window = 0;
+    context.last_nonce = 0;
+    context.commitment_updated_at = 0;
+    context.parent_context = None;
+    context.child_count = 0;
+    context.live_children = 0;
+    context.verifier_pubkey = None;
+    context.label = label;
+    context.epoch_spent = 0;
+    context.last_epoch = 0;
+    context.max_per_epoch = 0;
+    context.delegate = None;
+    context.linkability_score = 0;
+    context.score_updated_at = 0;
+    context.min_per_transaction = min_per_transaction;
+    context.spend_count = 0;
+    context.max_spend_count = 0;
+    context.burn_proof = None;
+    context.created_slot = clock.slot;
+    context.revoked = false;
+    context.revoked_at = 0;
+    context.config_sealed = false;
+    context.one_time_override = 0;
+    context.override_expires_at = 0;
+    context.reserved_space = reserved_space;
+    context.spend_multiple = 0;
+    context.spend_auth_code_hash = None;
+    context.auth_code_expires_at = 0;
+    context.auth_code_threshold = 0;
+    context.signature_binding = None;
+    context.event_digest = [0u8; 32];
+    context.event_tag = event_tag.filter(|tag| *tag != 0); // 0 is treated as untagged
+    context.context_index = root.context_count;
+    context.bump = bump;
+    
+    root.context_count = root.context_count.checked_add(1).unwrap();
+    root.open_contexts = root.open_contexts.checked_add(1).unwrap();
+    root.last_context_created_at = now;
+    
+    match encryption {
+        // Never emit the root key here: indexers would link every encrypted context to it
+        Some(encryption) => emit_context!(context, EncryptedContextCreated {
+            context_identity: context.key(),
+            root_identity_hash: encryption.root_identity_hash,
+            context_type,
+            computation_id: encryption.computation_id,
+            label,
+            event_tag: context.event_tag,
+            created_slot: context.created_slot,
+            timestamp: context.created_at,
+        }),
+        None => emit_context!(context, ContextCreated {
+            root_identity: root.key(),
+            context_identity: context.key(),
+            context_type,
+            max_per_transaction,
+            context_index: context.context_index,
+            expires_at,
+            label,
+            event_tag: context.event_tag,
+            created_slot: context.created_slot,
+            timestamp: context.created_at,
+        }),
+    }
+    
+    Ok(CreatedContext {
+        context_identity: context.key(),
+        context_index: context.context_index,
+    })
+}
+
+// Helper to create a program-owned PDA the way Anchor's `init` does: lamports already
+// sent to the address (anyone can prefund a predictable PDA) are topped up rather
+// than making create_account fail
+fn create_pda_account<'info>(
+    payer: &AccountInfo<'info>,
+    account: &AccountInfo<'info>,
+    system_program: &AccountInfo<'info>,
+    space: usize,
+    signer_seeds: &[&[&[u8]]],
+) -> Result<()> {
+    let rent = Rent::get()?.minimum_balance(space);
+    let current_lamports = account.lamports();
+    
+    if current_lamports == 0 {
+        return system_program::create_account(
+            CpiContext::new_with_signer(
+                system_program.clone(),
+                system_program::CreateAccount { from: payer.clone(), to: account.clone() },
+                signer_seeds,
+            ),
+            rent,
+            space as u64,
+            &crate::ID,
+        );
+    }
+    
+    let top_up = rent.saturating_sub(current_lamports);
+    if top_up > 0 {
+        system_program::transfer(
+            CpiContext::new(
+                system_program.clone(),
+                system_program::Transfer { from: payer.clone(), to: account.clone() },
+            ),
+            top_up,
+        )?;
+    }
+    system_program::allocate(
+        CpiContext::new_with_signer(
+            system_program.clone(),
+            system_program::Allocate { account_to_allocate: account.clone() },
+            signer_seeds,
+        ),
+        space as u64,
+    )?;
+    system_program::assign(
+        CpiContext::new_with_signer(
+            system_program.clone(),
+            system_program::Assign { account_to_assign: account.clone() },
+            signer_seeds,
+        ),
+        &crate::ID,
+    )
+}
+
+// Helper shared by the root constructors: fills in a fresh single-owner root
+fn init_root_identity(
+    root: &mut Account<RootIdentity>,
+    user: Pubkey,
+    bump: u8,
+    privacy_level: u8,
+    max_contexts: u16,
+) -> Result<()> {
+    require!(privacy_level <= 4, PrismError::InvalidPrivacyLevel);
+    
+    root.version = ROOT_IDENTITY_VERSION;
+    root.owner = user;
+    root.creator = user;
+    root.pending_owner = None;
+    let clock = Clock::get()?;
+    root.created_at = clock.unix_timestamp;
+    root.created_slot = clock.slot;
+    root.privacy_level = privacy_level;
+    root.context_count = 0;
+    root.max_contexts = if max_contexts == 0 { u16::MAX } else { max_contexts };
+    root.encryption_epoch = 0;
+    root.privacy_policy_hash = None;
+    root.emergency_contact_hash = [0u8; 32];
+    root.reactivation_cooldown = 0;
+    root.global_spend_limit = 0;
+    root.global_spent = 0;
+    root.frozen = false;
+    root.guardians = [Pubkey::default(); MAX_GUARDIANS];
+    root.recovery_threshold = 0;
+    root.recovery_new_owner = None;
+    root.recovery_initiated_at = 0;
+    root.recovery_approvals = 0;
+    root.bump = bump;
+    root.revoked_count = 0;
+    root.type_defaults = [0; 6];
+    root.contexts_merkle_root = [0u8; 32];
+    root.pending_privacy_level = None;
+    root.privacy_change_eta = 0;
+    root.privacy_downgrade_timelock = 0;
+    root.last_context_created_at = 0;
+    root.creation_cooldown = 0;
+    root.owners = [Pubkey::default(); MAX_OWNERS];
+    root.owner_count = 0;
+    root.threshold = 0;
+    root.open_contexts = 0;
+    
+    emit!(RootIdentityCreated {
+        owner: root.owner,
+        privacy_level,
+        created_slot: root.created_slot,
+        timestamp: root.created_at,
+    });
+    
+    Ok(())
 }
 
-impl ContextIdentity {
-    // Updated size: discriminator (8) + root_identity (32) + root_identity_hash (1 + 32) + 
-    // encryption_commitment (1 + 32) + context_type (1) + created_at (8) + max_per_transaction (8) + 
-    // total_spent (8) + revoked (1) + context_index (2) + bump (1)
-    pub const SIZE: usize = 8 + 32 + 33 + 33 + 1 + 8 + 8 + 8 + 1 + 2 + 1; // 135 bytes
+// Helper behind require_owner_quorum!: counts `user` and every distinct owner that
+// signed via remaining_accounts against the root's multisig threshold
+fn owner_quorum_met(root: &RootIdentity, user: &Pubkey, remaining_accounts: &[AccountInfo]) -> bool {
+    if root.threshold <= 1 {
+        return true;
+    }
+    let mut approvals: Vec<Pubkey> = vec![*user];
+    for account in remaining_accounts.iter().filter(|account| account.is_signer) {
+        if root.is_owner(account.key) && !approvals.contains(account.key) {
+            approvals.push(*account.key);
+        }
+    }
+    approvals.len() >= root.threshold as usize
 }
 
-// ============================================================================
-// CONTEXT TYPES (for reference)
-// ============================================================================
+// Helper to check an account is an initialized mint owned by the SPL Token program
+fn is_token_mint(account: &AccountInfo) -> bool {
+    *account.owner == anchor_spl::token::ID
+        && account
+            .try_borrow_data()
+            .map(|data| Mint::unpack(&data).is_ok())
+            .unwrap_or(false)
+}
 
-#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, PartialEq, Eq)]
-pub enum ContextType {
-    DeFi = 0,        // Dark pool trading, swaps
-    Social = 1,      // Social interactions
-    Gaming = 2,      // Gaming activities  
-    Professional = 3, // Work-related
-    Temporary = 4,   // Auto-burn after use
-    Public = 5,      // Flex mode - fully public
+// Helper to check an actual amount is within `slippage_bps` of the expected amount
+// (u128 intermediate so large amounts can't overflow)
+fn within_slippage(amount: u64, expected: u64, slippage_bps: u16) -> bool {
+    let tolerance = (expected as u128) * (slippage_bps as u128) / 10_000;
+    (amount as u128).abs_diff(expected as u128) <= tolerance
 }
 
-#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, PartialEq, Eq)]
-pub enum PrivacyLevel {
-    Maximum = 0,     // Full anonymity
-    High = 1,        // Minimal disclosure
-    Medium = 2,      // Balanced
-    Low = 3,         // More transparent
-    Public = 4,      // Fully public
+// Helper to check a context account (e.g. from remaining_accounts) is the PDA
+// derived from this root, so plaintext and encrypted contexts are both covered
+// (hash-seeded encrypted contexts via their stored pda_seed)
+fn context_belongs_to_root(context: &Account<ContextIdentity>, root_key: &Pubkey) -> bool {
+    Pubkey::create_program_address(
+        &[
+            context.seed_prefix(root_key),
+            &context.seed_key(root_key),
+            &context.context_index.to_le_bytes(),
+            &[context.bump],
+        ],
+        &crate::ID,
+    )
+    .map(|expected| expected == context.key())
+    .unwrap_or(false)
 }
 
-// ============================================================================
-// EVENTS
-// ============================================================================
+// Checks the context or sub-context stored at `key` was created under `root_key`,
+// for accounts that aren't deserialized through Anchor
+fn identity_belongs_to_root(context: &ContextIdentity, key: &Pubkey, root_key: &Pubkey) -> bool {
+    let expected = match context.parent_context {
+        Some(parent) => {
+            let bound = context.root_identity == *root_key
+                || context.root_identity_hash.is_some_and(|root_hash| root_hash_matches(root_key, &root_hash));
+            if !bound {
+                return false;
+            }
+            Pubkey::create_program_address(
+                &[b"sub_context", parent.as_ref(), &context.context_index.to_le_bytes(), &[context.bump]],
+                &crate::ID,
+            )
+        }
+        None => Pubkey::create_program_address(
+            &[
+                context.seed_prefix(root_key),
+                &context.seed_key(root_key),
+                &context.context_index.to_le_bytes(),
+                &[context.bump],
+            ],
+            &crate::ID,
+        ),
+    };
+    expected.map(|expected| expected == *key).unwrap_or(false)
+}
 
-#[event]
-pub struct RootIdentityCreated {
-    pub owner: Pubkey,
-    pub privacy_level: u8,
-    pub timestamp: i64,
+// Helper to decode a ContextIdentity account (discriminator included) written
+// under any layout version, returning the version it was written with
+fn read_any_context(data: &[u8]) -> Result<(u8, ContextIdentity)> {
+    let (from_version, context) = if data.len() == LegacyContextIdentity::SIZE {
+        let legacy = LegacyContextIdentity::deserialize(&mut &data[8..])?;
+        (0, legacy.upgrade())
+    } else {
+        // Zero was never a valid version byte
+        require!(data.len() > 8 && data[8] >= 1, PrismError::UnsupportedVersion);
+        (data[8], read_padded::<ContextIdentity>(data, ContextIdentity::SIZE)?)
+    };
+    require!(from_version <= CONTEXT_IDENTITY_VERSION, PrismError::UnsupportedVersion);
+    Ok((from_version, context))
 }
 
-#[event]
-pub struct ContextCreated {
-    pub root_identity: Pubkey,
-    pub context_identity: Pubkey,
-    pub context_type: u8,
-    pub max_per_transaction: u64,
-    pub context_index: u16,
-    pub timestamp: i64,
+// Helper for migrate_account: counts the distinct context and sub-context accounts
+// of `root_key` passed in, whatever layout they are still on
+fn count_open_contexts(root_key: &Pubkey, accounts: &[AccountInfo]) -> Result<u32> {
+    let mut seen: Vec<Pubkey> = Vec::with_capacity(accounts.len());
+    for account in accounts {
+        require!(*account.owner == crate::ID, PrismError::ContextMismatch);
+        let data = account.try_borrow_data()?;
+        require!(
+            data.len() > 8 && data[..8] == *ContextIdentity::DISCRIMINATOR,
+            PrismError::ContextMismatch
+        );
+        let (_, context) = read_any_context(&data)?;
+        require!(
+            identity_belongs_to_root(&context, account.key, root_key) && !seen.contains(account.key),
+            PrismError::ContextMismatch
+        );
+        seen.push(*account.key);
+    }
+    Ok(seen.len() as u32)
 }
 
-#[event]
-pub struct ContextRevoked {
-    pub root_identity: Pubkey,
-    pub context_identity: Pubkey,
-    pub context_type: u8,
-    pub total_spent: u64,
-    pub timestamp: i64,
+// Helper for migrate_account: counts the distinct, unrevoked sub-contexts of
+// `parent_key` passed in, whatever layout they are still on
+fn count_live_children(parent_key: &Pubkey, accounts: &[AccountInfo]) -> Result<u16> {
+    let mut seen: Vec<Pubkey> = Vec::with_capacity(accounts.len());
+    let mut live: u16 = 0;
+    for account in accounts {
+        require!(*account.owner == crate::ID, PrismError::ContextMismatch);
+        let data = account.try_borrow_data()?;
+        require!(
+            data.len() > 8 && data[..8] == *ContextIdentity::DISCRIMINATOR,
+            PrismError::ContextMismatch
+        );
+        let (_, child) = read_any_context(&data)?;
+        let spawned_here = child.parent_context == Some(*parent_key)
+            && Pubkey::create_program_address(
+                &[b"sub_context", parent_key.as_ref(), &child.context_index.to_le_bytes(), &[child.bump]],
+                &crate::ID,
+            )
+            .is_ok_and(|expected| expected == *account.key);
+        require!(spawned_here && !seen.contains(account.key), PrismError::ContextMismatch);
+        seen.push(*account.key);
+        if !child.revoked {
+            live += 1;
+        }
+    }
+    Ok(live)
 }
 
-#[event]
-pub struct SpendingRecorded {
-    pub context_identity: Pubkey,
-    pub amount: u64,
-    pub total_spent: u64,
-    pub timestamp: i64,
+// Helper to decode an account written under an older, shorter layout: the missing
+// tail reads as zeros, i.e. each appended field's default
+fn read_padded<T: AnchorDeserialize>(data: &[u8], size: usize) -> Result<T> {
+    let mut padded = data.to_vec();
+    if padded.len() < size {
+        padded.resize(size, 0);
+    }
+    Ok(T::deserialize(&mut &padded[8..])?)
 }
 
-#[event]
-pub struct PrivacyLevelUpdated {
-    pub root_identity: Pubkey,
-    pub old_level: u8,
-    pub new_level: u8,
-    pub timestamp: i64,
+// Checks a sub-context passed via remaining_accounts was spawned from `parent_key`
+fn sub_context_belongs_to_parent(child: &Account<ContextIdentity>, parent_key: &Pubkey) -> bool {
+    child.parent_context == Some(*parent_key)
+        && Pubkey::create_program_address(
+            &[
+                b"sub_context",
+                parent_key.as_ref(),
+                &child.context_index.to_le_bytes(),
+                &[child.bump],
+            ],
+            &crate::ID,
+        )
+        .map(|expected| expected == child.key())
+        .unwrap_or(false)
 }
 
-// ============================================================================
-// ERRORS
-// ============================================================================
+// Helper shared by record_spending and record_spending_token; the caller's
+// account constraints have already checked the context's mint
+#[allow(clippy::too_many_arguments)]
+fn apply_spending(
+    root: &mut Account<RootIdentity>,
+    context: &mut Account<ContextIdentity>,
+    amount: u64,
+    auth_code: Option<Vec<u8>>,
+    expected_amount: Option<u64>,
+    max_slippage_bps: Option<u16>,
+    hook_data: Option<[u8; 32]>,
+    target_program: Pubkey,
+    nonce: u64,
+) -> Result<()> {
+    let clock = Clock::get()?;
+    let now = clock.unix_timestamp;
+    
+    require!(!root.frozen, PrismError::RootFrozen);
+    require!(!context.revoked, PrismError::ContextRevoked);
+    require!(!context.is_expired(now), PrismError::ContextExpired);
+    require!(context.allows_program(&target_program), PrismError::ProgramNotAllowed);
+    require!(context.is_aligned(amount), PrismError::AmountNotAligned);
+    require!(amount >= context.min_per_transaction, PrismError::BelowTransactionMinimum);
+    require!(
+        context.max_spend_count == 0 || context.spend_count < context.max_spend_count,
+        PrismError::SpendCountExceeded
+    );
+    
+    if nonce != 0 {
+        require!(nonce > context.last_nonce, PrismError::StaleNonce);
+        context.last_nonce = nonce;
+    }
+    
+    if let Some(expected) = expected_amount {
+        require!(
+            within_slippage(amount, expected, max_slippage_bps.unwrap_or(0)),
+            PrismError::SlippageExceeded
+        );
+    }
+    
+    context.consume_window(amount, now)?;
+    context.consume_epoch(amount, clock.epoch)?;
+    
+    // Root-wide cap across every lamport-denominated context
+    if context.mint.is_none() {
+        require!(root.global_allows(amount), PrismError::ExceedsGlobalLimit);
+        root.global_spent = root.global_spent.checked_add(amount)
+            .ok_or(PrismError::SpendingOverflow)?;
+    }
+    
+    // Amounts above the per-tx limit may only go through a one-time override,
+    // which is cleared as soon as it is used
+    let override_consumed = amount > context.max_per_transaction;
+    if override_consumed {
+        require!(
+            context.override_covers(amount, now),
+            PrismError::ExceedsTransactionLimit
+        );
+        context.one_time_override = 0;
+        context.override_expires_at = 0;
+    }
+    
+    // Large spends need a valid, unexpired auth code, which is single-use
+    if context.auth_code_threshold > 0 && amount > context.auth_code_threshold {
+        let code_valid = match (context.spend_auth_code_hash, auth_code) {
+            (Some(code_hash), Some(code)) => {
+                now < context.auth_code_expires_at && hash(&code).to_bytes() == code_hash
+            }
+            _ => false,
+        };
+        require!(code_valid, PrismError::InvalidAuthCode);
+        
+        context.spend_auth_code_hash = None;
+        context.auth_code_expires_at = 0;
+        
+        emit_context!(context, AuthCodeConsumed {
+            context_identity: context.key(),
+            amount,
+            event_tag: context.event_tag,
+            timestamp: now,
+        });
+    }
+    
+    context.total_spent = context.total_spent.checked_add(amount)
+        .ok_or(PrismError::SpendingOverflow)?;
+    context.spend_count = context.spend_count.saturating_add(1);
+    
+    // Decay first so the new spend is weighed against current exposure only
+    context.linkability_score = context
+        .linkability_at(now)
+        .saturating_add(amount / LINKABILITY_AMOUNT_UNIT);
+    context.score_updated_at = now;
+    
+    emit_context!(context, SpendingRecorded {
+        context_identity: context.key(),
+        amount,
+        total_spent: context.total_spent,
+        spend_count: context.spend_count,
+        override_consumed,
+        expected_amount,
+        mint: context.mint,
+        event_tag: context.event_tag,
+        timestamp: now,
+    });
+    
+    if let Some(hook_data) = hook_data {
+        emit_context!(context, SpendHook {
+            context_identity: context.key(),
+            amount,
+            hook_data,
+            event_tag: context.event_tag,
+            timestamp: now,
+        });
+    }
+    
+    Ok(())
+}
 
-// Helper function to hash root identity
-fn hash_root_identity(root_pubkey: &Pubkey) -> [u8; 32] {
-    let hash_result = hash(&root_pubkey.to_bytes());
-    hash_result.to_bytes()
+// Helper to charge a sub-context spend to its parent once check_context_spend passed:
+// window, epoch, override, totals and linkability move as for a direct spend
+fn roll_up_spending(parent: &mut ContextIdentity, amount: u64, clock: &Clock) -> Result<()> {
+    let now = clock.unix_timestamp;
+    // The auth code is presented for the child, so large spends can't clear the parent's gate
+    require!(
+        parent.auth_code_threshold == 0 || amount <= parent.auth_code_threshold,
+        PrismError::InvalidAuthCode
+    );
+    parent.consume_window(amount, now)?;
+    parent.consume_epoch(amount, clock.epoch)?;
+    
+    // check_context_spend only let this through if the parent's override covers it
+    if amount > parent.max_per_transaction {
+        parent.one_time_override = 0;
+        parent.override_expires_at = 0;
+    }
+    
+    parent.total_spent = parent.total_spent.checked_add(amount)
+        .ok_or(PrismError::SpendingOverflow)?;
+    parent.spend_count = parent.spend_count.saturating_add(1);
+    parent.linkability_score = parent
+        .linkability_at(now)
+        .saturating_add(amount / LINKABILITY_AMOUNT_UNIT);
+    parent.score_updated_at = now;
+    Ok(())
+}
+
+// Helper to grow a program-owned account to `new_size`, topping up rent from
+// `payer` first; the new bytes are zero-initialized
+fn grow_account<'info>(
+    account: &AccountInfo<'info>,
+    payer: &AccountInfo<'info>,
+    system_program: &AccountInfo<'info>,
+    new_size: usize,
+) -> Result<()> {
+    let required = Rent::get()?.minimum_balance(new_size);
+    let shortfall = required.saturating_sub(account.lamports());
+    if shortfall > 0 {
+        system_program::transfer(
+            CpiContext::new(
+                system_program.clone(),
+                system_program::Transfer {
+                    from: payer.clone(),
+                    to: account.clone(),
+                },
+            ),
+            shortfall,
+        )?;
+    }
+    account.realloc(new_size, true)?;
+    Ok(())
 }
 
 #[error_code]
@@ -533,4 +6289,471 @@ pub enum PrismError {
     
     #[msg("Invalid root identity hash: Hash does not match root identity PDA")]
     InvalidRootHash,
+    
+    #[msg("Context configuration is sealed and cannot be changed")]
+    ContextSealed,
+    
+    #[msg("Encryption epoch overflow")]
+    EpochOverflow,
+    
+    #[msg("Invalid override expiry: Must be in the future")]
+    InvalidOverrideExpiry,
+    
+    #[msg("Reserved space exceeds the maximum allowed")]
+    ReservedSpaceTooLarge,
+    
+    #[msg("Batch too large: Too many accounts for one instruction")]
+    BatchTooLarge,
+    
+    #[msg("Context is not encrypted")]
+    ContextNotEncrypted,
+    
+    #[msg("Computation mismatch: Context was not produced by this computation")]
+    ComputationMismatch,
+    
+    #[msg("Invalid auth code: Missing, expired, or does not match")]
+    InvalidAuthCode,
+    
+    #[msg("Invalid auth code expiry: Must be in the future")]
+    InvalidAuthCodeExpiry,
+    
+    #[msg("Nothing to sweep: Payment address has no lamports")]
+    NothingToSweep,
+    
+    #[msg("Slippage exceeded: Amount differs from expected by more than allowed")]
+    SlippageExceeded,
+    
+    #[msg("Invalid ring size: Must contain between 1 and MAX_RING_SIZE roots")]
+    InvalidRingSize,
+    
+    #[msg("Context has spending history and must be revoked before closing")]
+    RequiresGracePeriod,
+    
+    #[msg("Missing or malformed Ed25519 signature instruction")]
+    InvalidSignatureInstruction,
+    
+    #[msg("Signature mismatch: Signer or message does not match")]
+    SignatureMismatch,
+    
+    #[msg("Archive account must be passed if and only if archive_on_close is set")]
+    ArchiveAccountMismatch,
+    
+    #[msg("Invalid expiry: Must be in the future")]
+    InvalidExpiry,
+    
+    #[msg("Context has expired and cannot be used")]
+    ContextExpired,
+    
+    #[msg("Amount is not a multiple of the context's spend multiple")]
+    AmountNotAligned,
+    
+    #[msg("Invalid spend multiple: Must not exceed max_per_transaction")]
+    InvalidSpendMultiple,
+    
+    #[msg("Context has not expired")]
+    ContextNotExpired,
+    
+    #[msg("Mint does not match the context's spending denomination")]
+    MintMismatch,
+    
+    #[msg("Amount exceeds the rolling window limit for this context")]
+    ExceedsWindowLimit,
+    
+    #[msg("Invalid window duration: Must not be negative")]
+    InvalidWindowDuration,
+    
+    #[msg("Context is not revoked")]
+    ContextNotRevoked,
+    
+    #[msg("Reactivation cooldown has not elapsed since revocation")]
+    ReactivationCooldownActive,
+    
+    #[msg("Invalid cooldown: Must not be negative")]
+    InvalidCooldown,
+    
+    #[msg("New owner is the same as the current owner")]
+    SameOwner,
+    
+    #[msg("No ownership transfer is pending")]
+    NoPendingOwner,
+    
+    #[msg("Amount exceeds the global spending limit for this root")]
+    ExceedsGlobalLimit,
+    
+    #[msg("Root has reached its maximum number of contexts")]
+    MaxContextsReached,
+    
+    #[msg("Batch length mismatch: count, specs and accounts must agree")]
+    BatchLengthMismatch,
+    
+    #[msg("Root identity is frozen")]
+    RootFrozen,
+    
+    #[msg("Root identity is not frozen")]
+    RootNotFrozen,
+    
+    #[msg("Signer is not a guardian of this root")]
+    NotAGuardian,
+    
+    #[msg("Invalid guardians: Must be distinct, exclude the owner and cover the threshold")]
+    InvalidGuardianConfig,
+    
+    #[msg("Guardian recovery is not configured for this root")]
+    RecoveryNotConfigured,
+    
+    #[msg("A recovery is already in progress")]
+    RecoveryInProgress,
+    
+    #[msg("No recovery is pending")]
+    NoRecoveryPending,
+    
+    #[msg("Not enough guardians have approved the recovery")]
+    RecoveryThresholdNotMet,
+    
+    #[msg("Recovery timelock has not elapsed")]
+    RecoveryTimelockActive,
+    
+    #[msg("Target program is not allowed for this context")]
+    ProgramNotAllowed,
+    
+    #[msg("Unsupported account version: Migrate the account first")]
+    UnsupportedVersion,
+    
+    #[msg("Account is already at the current version")]
+    AlreadyMigrated,
+    
+    #[msg("Context is already at least the current size")]
+    ResizeWouldShrink,
+    
+    #[msg("Stale nonce: Must be greater than the last nonce used")]
+    StaleNonce,
+    
+    #[msg("Program is paused")]
+    ProgramPaused,
+    
+    #[msg("Program is not paused")]
+    ProgramNotPaused,
+    
+    #[msg("Refund exceeds the amount spent through this context")]
+    RefundUnderflow,
+    
+    #[msg("Invalid label: Must be UTF-8 followed only by zero padding")]
+    InvalidLabel,
+    
+    #[msg("Root still has open context accounts; revoke and close them all before closing")]
+    ActiveContextsRemain,
+    
+    #[msg("Amount exceeds remaining epoch limit")]
+    ExceedsEpochLimit,
+    
+    #[msg("Merkle proof exceeds MAX_MERKLE_DEPTH")]
+    MerkleProofTooLong,
+    
+    #[msg("Signer is neither the root owner nor the context's delegate")]
+    NotDelegate,
+    
+    #[msg("Lowering privacy requires propose_privacy_change and its timelock")]
+    PrivacyDowngradeTimelocked,
+    
+    #[msg("Proposed privacy level is not less private than the current one")]
+    NotAPrivacyDowngrade,
+    
+    #[msg("No privacy change is pending")]
+    NoPendingPrivacyChange,
+    
+    #[msg("Invalid privacy timelock: Must be at least MIN_PRIVACY_DOWNGRADE_TIMELOCK")]
+    InvalidPrivacyTimelock,
+    
+    #[msg("Context type is not allowed at this root's privacy level")]
+    ContextTypeNotAllowedAtPrivacyLevel,
+    
+    #[msg("Amount is below the context's per-transaction minimum")]
+    BelowTransactionMinimum,
+    
+    #[msg("Invalid minimum: Must not exceed max_per_transaction")]
+    InvalidTransactionMinimum,
+    
+    #[msg("Context has used up its allowed number of spends")]
+    SpendCountExceeded,
+    
+    #[msg("Context creation cooldown has not elapsed")]
+    CreationCooldownActive,
+    
+    #[msg("Invalid limit: Must cover min_per_transaction and spend_multiple")]
+    InvalidTransactionLimit,
+    
+    #[msg("Invalid multisig: 2 to MAX_OWNERS distinct owners including the signer, threshold within range")]
+    InvalidMultisigConfig,
+    
+    #[msg("Not enough multisig owners signed")]
+    MultisigThresholdNotMet,
+    
+    #[msg("Refund amount must be nonzero")]
+    ZeroRefund,
+    
+    #[msg("Refund exceeds the spend counted in the current window or epoch")]
+    RefundExceedsCurrentSpend,
+    
+    #[msg("Context still has live sub-contexts: revoke them first")]
+    LiveSubContextsRemain,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn context_with_multiple(spend_multiple: u64) -> ContextIdentity {
+        ContextIdentity {
+            spend_multiple,
+            ..Default::default()
+        }
+    }
+    
+    #[test]
+    fn is_aligned_accepts_everything_when_unset() {
+        let context = context_with_multiple(0);
+        assert!(context.is_aligned(0));
+        assert!(context.is_aligned(1));
+        assert!(context.is_aligned(u64::MAX));
+    }
+    
+    #[test]
+    fn is_aligned_accepts_multiples() {
+        let context = context_with_multiple(1_000);
+        assert!(context.is_aligned(0));
+        assert!(context.is_aligned(1_000));
+        assert!(context.is_aligned(250_000));
+    }
+    
+    #[test]
+    fn is_aligned_rejects_non_multiples() {
+        let context = context_with_multiple(1_000);
+        assert!(!context.is_aligned(1));
+        assert!(!context.is_aligned(999));
+        assert!(!context.is_aligned(1_001));
+        assert!(!context.is_aligned(u64::MAX));
+    }
+
+    fn plaintext_context() -> ContextIdentity {
+        ContextIdentity {
+            root_identity: Pubkey::new_unique(),
+            ..Default::default()
+        }
+    }
+
+    fn encrypted_context() -> ContextIdentity {
+        ContextIdentity {
+            root_identity_hash: Some([1; 32]),
+            encryption_commitment: Some([2; 32]),
+            encryption_epoch: 1,
+            computation_id: Some([3; 32]),
+            ..Default::default()
+        }
+    }
+    
+    #[test]
+    fn state_violations_clean() {
+        assert_eq!(plaintext_context().state_violations(), 0);
+        assert_eq!(encrypted_context().state_violations(), 0);
+        
+        let context = ContextIdentity {
+            one_time_override: 500,
+            override_expires_at: 1_000,
+            spend_auth_code_hash: Some([4; 32]),
+            auth_code_expires_at: 1_000,
+            event_tag: Some(7),
+            reserved_space: MAX_RESERVED_SPACE,
+            ..plaintext_context()
+        };
+        assert_eq!(context.state_violations(), 0);
+    }
+    
+    #[test]
+    fn state_violations_encryption_fields() {
+        let plaintext_with_commitment = ContextIdentity {
+            encryption_commitment: Some([2; 32]),
+            ..plaintext_context()
+        };
+        assert_eq!(plaintext_with_commitment.state_violations(), VIOLATION_ENCRYPTION_FIELDS);
+        
+        let plaintext_without_root = ContextIdentity::default();
+        assert_eq!(plaintext_without_root.state_violations(), VIOLATION_ENCRYPTION_FIELDS);
+        
+        let encrypted_with_root = ContextIdentity {
+            root_identity: Pubkey::new_unique(),
+            ..encrypted_context()
+        };
+        assert_eq!(encrypted_with_root.state_violations(), VIOLATION_ENCRYPTION_FIELDS);
+        
+        let encrypted_without_commitment = ContextIdentity {
+            encryption_commitment: None,
+            ..encrypted_context()
+        };
+        assert_eq!(encrypted_without_commitment.state_violations(), VIOLATION_ENCRYPTION_FIELDS);
+    }
+    
+    #[test]
+    fn state_violations_plaintext_encryption_data() {
+        let with_epoch = ContextIdentity {
+            encryption_epoch: 1,
+            ..plaintext_context()
+        };
+        assert_eq!(with_epoch.state_violations(), VIOLATION_PLAINTEXT_ENCRYPTION_DATA);
+        
+        let with_computation = ContextIdentity {
+            computation_id: Some([3; 32]),
+            ..plaintext_context()
+        };
+        assert_eq!(with_computation.state_violations(), VIOLATION_PLAINTEXT_ENCRYPTION_DATA);
+    }
+    
+    #[test]
+    fn state_violations_override() {
+        let context = ContextIdentity {
+            one_time_override: 500,
+            ..plaintext_context()
+        };
+        assert_eq!(context.state_violations(), VIOLATION_OVERRIDE);
+    }
+    
+    #[test]
+    fn state_violations_auth_code() {
+        let hash_without_expiry = ContextIdentity {
+            spend_auth_code_hash: Some([4; 32]),
+            ..plaintext_context()
+        };
+        assert_eq!(hash_without_expiry.state_violations(), VIOLATION_AUTH_CODE);
+        
+        let expiry_without_hash = ContextIdentity {
+            auth_code_expires_at: 1_000,
+            ..plaintext_context()
+        };
+        assert_eq!(expiry_without_hash.state_violations(), VIOLATION_AUTH_CODE);
+    }
+    
+    #[test]
+    fn state_violations_event_tag() {
+        let context = ContextIdentity {
+            event_tag: Some(0),
+            ..plaintext_context()
+        };
+        assert_eq!(context.state_violations(), VIOLATION_EVENT_TAG);
+    }
+    
+    #[test]
+    fn state_violations_reserved_space() {
+        let context = ContextIdentity {
+            reserved_space: MAX_RESERVED_SPACE + 1,
+            ..plaintext_context()
+        };
+        assert_eq!(context.state_violations(), VIOLATION_RESERVED_SPACE);
+    }
+    
+    #[test]
+    fn state_violations_accumulate() {
+        let context = ContextIdentity {
+            one_time_override: 500,
+            event_tag: Some(0),
+            reserved_space: MAX_RESERVED_SPACE + 1,
+            ..plaintext_context()
+        };
+        assert_eq!(
+            context.state_violations(),
+            VIOLATION_OVERRIDE | VIOLATION_EVENT_TAG | VIOLATION_RESERVED_SPACE
+        );
+    }
+    
+    const COMMITMENT: [u8; 32] = [7; 32];
+
+    /// Run `check` against `context` loaded as a program-owned account
+    fn with_account<R>(context: &ContextIdentity, check: impl FnOnce(&Account<ContextIdentity>) -> R) -> R {
+        let key = Pubkey::new_unique();
+        let mut lamports = 1;
+        let mut data = Vec::new();
+        context.try_serialize(&mut data).unwrap();
+        let info = AccountInfo::new(&key, false, false, &mut lamports, &mut data, &crate::ID, false, 0);
+        check(&Account::try_from(&info).unwrap())
+    }
+
+    fn verifiable_context(verifier: Pubkey) -> ContextIdentity {
+        ContextIdentity {
+            encryption_commitment: Some(COMMITMENT),
+            verifier_pubkey: Some(verifier),
+            ..encrypted_context()
+        }
+    }
+    
+    #[test]
+    fn commitment_proof_message_binds_every_input() {
+        let context = Pubkey::new_unique();
+        let binding_key = Pubkey::new_unique();
+        let message = commitment_proof_message(&context, &COMMITMENT, &binding_key);
+        
+        assert_eq!(message, commitment_proof_message(&context, &COMMITMENT, &binding_key));
+        assert_ne!(message, commitment_proof_message(&Pubkey::new_unique(), &COMMITMENT, &binding_key));
+        assert_ne!(message, commitment_proof_message(&context, &[8; 32], &binding_key));
+        assert_ne!(message, commitment_proof_message(&context, &COMMITMENT, &Pubkey::new_unique()));
+        // Domain-separated from a bare hash of the same fields
+        assert_ne!(
+            message,
+            hashv(&[context.as_ref(), &COMMITMENT, binding_key.as_ref()]).to_bytes()
+        );
+    }
+    
+    #[test]
+    fn commitment_proof_valid_accepts_the_verifiers_proof() {
+        let verifier = Pubkey::new_unique();
+        with_account(&verifiable_context(verifier), |context| {
+            let message = commitment_proof_message(&context.key(), &COMMITMENT, &context.key());
+            assert!(commitment_proof_valid(context, &COMMITMENT, &context.key(), &verifier, &message));
+        });
+    }
+    
+    #[test]
+    fn commitment_proof_valid_rejects_mismatches() {
+        let verifier = Pubkey::new_unique();
+        with_account(&verifiable_context(verifier), |context| {
+            let key = context.key();
+            let message = commitment_proof_message(&key, &COMMITMENT, &key);
+            
+            // Another commitment, even with a matching message
+            let other = [8; 32];
+            let other_message = commitment_proof_message(&key, &other, &key);
+            assert!(!commitment_proof_valid(context, &other, &key, &verifier, &other_message));
+            
+            // Bound to another account
+            let binding_key = Pubkey::new_unique();
+            let bound_message = commitment_proof_message(&key, &COMMITMENT, &binding_key);
+            assert!(!commitment_proof_valid(context, &COMMITMENT, &binding_key, &verifier, &bound_message));
+            
+            // Signed by someone other than the verifier
+            assert!(!commitment_proof_valid(context, &COMMITMENT, &key, &Pubkey::new_unique(), &message));
+            
+            // Signature over a different message
+            assert!(!commitment_proof_valid(context, &COMMITMENT, &key, &verifier, &[0; 32]));
+        });
+    }
+    
+    #[test]
+    fn commitment_proof_valid_needs_commitment_and_verifier() {
+        let verifier = Pubkey::new_unique();
+        
+        let without_verifier = ContextIdentity {
+            verifier_pubkey: None,
+            ..verifiable_context(verifier)
+        };
+        with_account(&without_verifier, |context| {
+            let message = commitment_proof_message(&context.key(), &COMMITMENT, &context.key());
+            assert!(!commitment_proof_valid(context, &COMMITMENT, &context.key(), &verifier, &message));
+        });
+        
+        let without_commitment = ContextIdentity {
+            encryption_commitment: None,
+            ..verifiable_context(verifier)
+        };
+        with_account(&without_commitment, |context| {
+            let message = commitment_proof_message(&context.key(), &COMMITMENT, &context.key());
+            assert!(!commitment_proof_valid(context, &COMMITMENT, &context.key(), &verifier, &message));
+        });
+    }
 }
@@ -0,0 +1,72 @@
+//! Minimal program integrating Prism through `prism::cpi`, the way a dark pool or
+//! payment program would. Only used by the integration tests in this crate.
+
+use anchor_lang::prelude::*;
+use prism::cpi::accounts::{CheckSpendingLimit, VerifyCommitment};
+use prism::program::Prism;
+use prism::{CommitmentVerification, ContextIdentity, RootIdentity};
+
+declare_id!("mrC8mD4GUAAWtJWq1319kWv9cSwxBbNSYyrYoH5u9d6");
+
+#[program]
+pub mod mock_caller {
+    use super::*;
+
+    /// Refuse to go ahead unless Prism allows `amount` to be spent towards this program
+    pub fn guarded_spend(ctx: Context<GuardedSpend>, amount: u64) -> Result<()> {
+        let cpi_ctx = CpiContext::new(
+            ctx.accounts.prism_program.to_account_info(),
+            CheckSpendingLimit {
+                context_identity: ctx.accounts.context_identity.to_account_info(),
+                root_identity: ctx.accounts.root_identity.to_account_info(),
+                user: ctx.accounts.user.to_account_info(),
+            },
+        );
+        prism::cpi::check_spending_limit(cpi_ctx, amount, crate::ID)
+    }
+
+    /// Verify a commitment through Prism and hand the decoded result back as our own return data
+    pub fn verify_through_prism(
+        ctx: Context<VerifyThroughPrism>,
+        commitment: [u8; 32],
+        binding_key: Pubkey,
+    ) -> Result<CommitmentVerification> {
+        let cpi_ctx = CpiContext::new(
+            ctx.accounts.prism_program.to_account_info(),
+            VerifyCommitment {
+                context_identity: ctx.accounts.context_identity.to_account_info(),
+                root_identity: ctx.accounts.root_identity.to_account_info(),
+                user: ctx.accounts.user.to_account_info(),
+                instructions: ctx.accounts.instructions.to_account_info(),
+            },
+        );
+        let verification = prism::cpi::verify_commitment(cpi_ctx, commitment, binding_key)?.get();
+
+        Ok(verification)
+    }
+}
+
+#[derive(Accounts)]
+pub struct GuardedSpend<'info> {
+    pub context_identity: Account<'info, ContextIdentity>,
+
+    pub root_identity: Account<'info, RootIdentity>,
+
+    pub user: Signer<'info>,
+
+    pub prism_program: Program<'info, Prism>,
+}
+
+#[derive(Accounts)]
+pub struct VerifyThroughPrism<'info> {
+    pub context_identity: Account<'info, ContextIdentity>,
+
+    pub root_identity: Account<'info, RootIdentity>,
+
+    pub user: Signer<'info>,
+
+    /// CHECK: Instructions sysvar, checked by Prism
+    pub instructions: UncheckedAccount<'info>,
+
+    pub prism_program: Program<'info, Prism>,
+}
@@ -0,0 +1,171 @@
+//! The mock caller integrating Prism through CPI, run against the in-process harness
+
+mod fixtures;
+mod harness;
+
+use anchor_lang::prelude::*;
+use anchor_lang::solana_program::entrypoint::ProgramResult;
+use anchor_lang::solana_program::hash::hashv;
+use anchor_lang::solana_program::instruction::Instruction;
+use anchor_lang::solana_program::{ed25519_program, sysvar};
+use anchor_lang::{InstructionData, ToAccountMetas};
+use fixtures::{
+    create_context, create_encrypted_context, prism_error, set_verifier_key, setup, Fixture,
+    MAX_PER_TRANSACTION,
+};
+use harness::{assert_ok, instructions_sysvar, process, program, readonly};
+use prism::{CommitmentVerification, ContextIdentity, PrismError};
+
+/// What the context's verifier signs: see commitment_proof_message in Prism
+fn commitment_proof_message(context: &Pubkey, commitment: &[u8; 32], binding_key: &Pubkey) -> [u8; 32] {
+    hashv(&[b"prism:commitment-proof:v1", context.as_ref(), commitment, binding_key.as_ref()]).to_bytes()
+}
+
+/// An Ed25519 program instruction carrying `signer`'s signature over `message`
+/// The runtime checks the signature itself, so its bytes don't matter here.
+fn ed25519_instruction(signer: &Pubkey, message: &[u8]) -> Instruction {
+    const PUBLIC_KEY_OFFSET: u16 = 16;
+    const SIGNATURE_OFFSET: u16 = PUBLIC_KEY_OFFSET + 32;
+    const MESSAGE_OFFSET: u16 = SIGNATURE_OFFSET + 64;
+
+    let mut data = vec![1, 0];
+    for offset in [
+        SIGNATURE_OFFSET,
+        u16::MAX,
+        PUBLIC_KEY_OFFSET,
+        u16::MAX,
+        MESSAGE_OFFSET,
+        message.len() as u16,
+        u16::MAX,
+    ] {
+        data.extend_from_slice(&offset.to_le_bytes());
+    }
+    data.extend_from_slice(signer.as_ref());
+    data.extend_from_slice(&[0; 64]);
+    data.extend_from_slice(message);
+
+    Instruction { program_id: ed25519_program::ID, accounts: vec![], data }
+}
+
+/// Send [ed25519 signature, mock verify_through_prism] and decode the mock's return data
+fn verify_through_prism(
+    fixture: &Fixture,
+    context: &AccountInfo<'static>,
+    signer: &Pubkey,
+    commitment: [u8; 32],
+) -> CommitmentVerification {
+    let binding_key = *context.key;
+    let message = commitment_proof_message(context.key, &commitment, &binding_key);
+    let data = mock_caller::instruction::VerifyThroughPrism { commitment, binding_key }.data();
+    let verify = Instruction {
+        program_id: mock_caller::ID,
+        accounts: mock_caller::accounts::VerifyThroughPrism {
+            context_identity: *context.key,
+            root_identity: *fixture.root.key,
+            user: *fixture.owner.key,
+            instructions: sysvar::instructions::ID,
+            prism_program: prism::ID,
+        }
+        .to_account_metas(None),
+        data: data.clone(),
+    };
+    let transaction = [ed25519_instruction(signer, &message), verify];
+
+    assert_ok(process(
+        &mock_caller::ID,
+        &[
+            readonly(context.clone()),
+            readonly(fixture.root.clone()),
+            readonly(fixture.owner.clone()),
+            instructions_sysvar(&transaction, 1),
+            program(prism::ID),
+        ],
+        &data,
+    ));
+
+    let (program_id, return_data) = harness::return_data().expect("no return data");
+    assert_eq!(program_id, mock_caller::ID);
+    CommitmentVerification::try_from_slice(&return_data).unwrap()
+}
+
+fn guarded_spend(fixture: &Fixture, context: &AccountInfo<'static>, amount: u64) -> ProgramResult {
+    process(
+        &mock_caller::ID,
+        &[
+            readonly(context.clone()),
+            readonly(fixture.root.clone()),
+            readonly(fixture.owner.clone()),
+            program(prism::ID),
+        ],
+        &mock_caller::instruction::GuardedSpend { amount }.data(),
+    )
+}
+
+#[test]
+fn guarded_spend_within_limits() {
+    let fixture = setup();
+    let (context, result) = create_context(&fixture, 0);
+    assert_ok(result);
+
+    assert_ok(guarded_spend(&fixture, &context, MAX_PER_TRANSACTION));
+}
+
+#[test]
+fn guarded_spend_surfaces_prism_errors() {
+    let fixture = setup();
+    let (context, result) = create_context(&fixture, 0);
+    assert_ok(result);
+
+    assert_eq!(
+        guarded_spend(&fixture, &context, MAX_PER_TRANSACTION + 1),
+        Err(prism_error(PrismError::ExceedsTransactionLimit))
+    );
+}
+
+#[test]
+fn guarded_spend_rejects_revoked_contexts() {
+    let fixture = setup();
+    let (context, result) = create_context(&fixture, 0);
+    assert_ok(result);
+
+    assert_ok(process(
+        &prism::ID,
+        &[fixture.owner.clone(), fixture.root.clone(), context.clone()],
+        &prism::instruction::RevokeContext {}.data(),
+    ));
+    assert!(harness::data::<ContextIdentity>(&context).revoked);
+
+    assert_eq!(
+        guarded_spend(&fixture, &context, 1),
+        Err(prism_error(PrismError::ContextRevoked))
+    );
+}
+
+#[test]
+fn verify_commitment_result_reaches_the_caller() {
+    let fixture = setup();
+    let commitment = [7; 32];
+    let (context, result) = create_encrypted_context(&fixture, commitment, 0);
+    assert_ok(result);
+    let verifier = Pubkey::new_unique();
+    set_verifier_key(&fixture, &context, verifier);
+
+    let verification = verify_through_prism(&fixture, &context, &verifier, commitment);
+
+    assert!(verification.is_valid);
+    assert_eq!(verification.binding_key, *context.key);
+}
+
+#[test]
+fn verify_commitment_reports_foreign_signers_as_invalid() {
+    let fixture = setup();
+    let commitment = [7; 32];
+    let (context, result) = create_encrypted_context(&fixture, commitment, 0);
+    assert_ok(result);
+    set_verifier_key(&fixture, &context, Pubkey::new_unique());
+
+    let verification = verify_through_prism(&fixture, &context, &Pubkey::new_unique(), commitment);
+
+    assert!(!verification.is_valid);
+    assert_eq!(verification.binding_key, *context.key);
+}
@@ -0,0 +1,318 @@
+//! Prism state the integration tests start from, created through Prism's own
+//! instructions against the harness
+
+// Each test binary uses a different subset
+#![allow(dead_code)]
+
+use anchor_lang::prelude::*;
+use anchor_lang::solana_program::entrypoint::ProgramResult;
+use anchor_lang::solana_program::hash::hashv;
+use anchor_lang::{system_program, InstructionData};
+use prism::{ContextIdentity, PrismError, ProgramConfig};
+
+use crate::harness::{self, assert_ok, empty, process, program, readonly, state, wallet};
+
+pub const MAX_PER_TRANSACTION: u64 = 1_000_000;
+
+#[derive(Clone)]
+pub struct Fixture {
+    pub owner: AccountInfo<'static>,
+    pub root: AccountInfo<'static>,
+    pub config: AccountInfo<'static>,
+    /// Co-owners of a multisig root signing alongside `owner`, appended to owner-gated calls
+    pub cosigners: Vec<AccountInfo<'static>>,
+}
+
+pub fn prism_error(error: PrismError) -> ProgramError {
+    anchor_lang::error::Error::from(error).into()
+}
+
+/// Assert `context` passes validate_state_consistency; closed accounts are skipped
+#[track_caller]
+pub fn assert_consistent(context: &AccountInfo) {
+    if context.data_is_empty() {
+        return;
+    }
+    let violations = harness::data::<ContextIdentity>(context).state_violations();
+    assert_eq!(violations, 0, "{} has state violations {violations:#b}", context.key);
+}
+
+/// Pass `result` through, checking every context it touched is consistent when it succeeded
+#[track_caller]
+pub fn checked(result: ProgramResult, contexts: &[&AccountInfo]) -> ProgramResult {
+    if result.is_ok() {
+        contexts.iter().for_each(|context| assert_consistent(context));
+    }
+    result
+}
+
+pub fn setup() -> Fixture {
+    setup_with_max_contexts(0)
+}
+
+/// A single-owner root capped at `max_contexts` (0 = unlimited)
+pub fn setup_with_max_contexts(max_contexts: u16) -> Fixture {
+    let fixture = empty_fixture();
+    assert_ok(create_root_with_max_contexts(&fixture, max_contexts));
+    fixture
+}
+
+/// A root owned by `owner` plus `co_owners`, any `threshold` of which must sign
+/// Every co-owner signs the fixture's owner-gated calls.
+pub fn setup_multisig(co_owners: usize, threshold: u8) -> Fixture {
+    let mut fixture = empty_fixture();
+    fixture.cosigners = (0..co_owners).map(|_| wallet(Pubkey::new_unique())).collect();
+    let mut owners = vec![*fixture.owner.key];
+    owners.extend(fixture.cosigners.iter().map(|cosigner| *cosigner.key));
+
+    assert_ok(process(
+        &prism::ID,
+        &[
+            fixture.owner.clone(),
+            fixture.root.clone(),
+            program(system_program::ID),
+            readonly(fixture.config.clone()),
+        ],
+        &prism::instruction::CreateMultisigRoot { privacy_level: 2, max_contexts: 0, owners, threshold }
+            .data(),
+    ));
+    fixture
+}
+
+/// `accounts` followed by the fixture's co-signers
+pub fn with_cosigners(fixture: &Fixture, accounts: &[AccountInfo<'static>]) -> Vec<AccountInfo<'static>> {
+    let mut accounts = accounts.to_vec();
+    accounts.extend_from_slice(&fixture.cosigners);
+    accounts
+}
+
+fn empty_fixture() -> Fixture {
+    let owner = wallet(Pubkey::new_unique());
+    let (root_key, _) = Pubkey::find_program_address(&[b"root", owner.key.as_ref()], &prism::ID);
+    let (config_key, config_bump) = Pubkey::find_program_address(&[b"config"], &prism::ID);
+    let config = state(
+        config_key,
+        prism::ID,
+        &ProgramConfig { admin: *owner.key, paused: false, bump: config_bump, verbose_events: false },
+        ProgramConfig::SIZE,
+    );
+    Fixture { owner, root: empty(root_key), config, cosigners: Vec::new() }
+}
+
+/// (Re-)create the fixture owner's root identity
+pub fn create_root_identity(fixture: &Fixture) -> ProgramResult {
+    create_root_with_max_contexts(fixture, 0)
+}
+
+fn create_root_with_max_contexts(fixture: &Fixture, max_contexts: u16) -> ProgramResult {
+    process(
+        &prism::ID,
+        &[
+            fixture.owner.clone(),
+            fixture.root.clone(),
+            program(system_program::ID),
+            readonly(fixture.config.clone()),
+        ],
+        // Medium: the lowest privacy level that allows plaintext contexts
+        &prism::instruction::CreateRootIdentity { privacy_level: 2, max_contexts }.data(),
+    )
+}
+
+/// create_context arguments the fixtures use unless a test overrides them
+pub fn context_args() -> prism::instruction::CreateContext {
+    prism::instruction::CreateContext {
+        context_type: 0,
+        max_per_transaction: MAX_PER_TRANSACTION,
+        reserved_space: 0,
+        event_tag: None,
+        expires_at: None,
+        mint: None,
+        allowed_program: None,
+        label: [0; 32],
+        min_per_transaction: 0,
+    }
+}
+
+pub fn create_context(fixture: &Fixture, index: u16) -> (AccountInfo<'static>, ProgramResult) {
+    create_context_with(fixture, index, context_args())
+}
+
+pub fn create_context_with(
+    fixture: &Fixture,
+    index: u16,
+    args: prism::instruction::CreateContext,
+) -> (AccountInfo<'static>, ProgramResult) {
+    let (context_key, _) = Pubkey::find_program_address(
+        &[b"context", fixture.root.key.as_ref(), &index.to_le_bytes()],
+        &prism::ID,
+    );
+    let context = empty(context_key);
+    let result = process(
+        &prism::ID,
+        &with_cosigners(
+            fixture,
+            &[
+                fixture.owner.clone(),
+                fixture.root.clone(),
+                context.clone(),
+                program(system_program::ID),
+                readonly(fixture.config.clone()),
+            ],
+        ),
+        &args.data(),
+    );
+    let result = checked(result, &[&context]);
+    (context, result)
+}
+
+pub fn create_encrypted_context(
+    fixture: &Fixture,
+    commitment: [u8; 32],
+    index: u16,
+) -> (AccountInfo<'static>, ProgramResult) {
+    let (context_key, _) = Pubkey::find_program_address(
+        &[prism::ENCRYPTED_CONTEXT_SEED, &commitment, &index.to_le_bytes()],
+        &prism::ID,
+    );
+    let context = empty(context_key);
+    let result = process(
+        &prism::ID,
+        &with_cosigners(
+            fixture,
+            &[
+                fixture.owner.clone(),
+                fixture.root.clone(),
+                context.clone(),
+                program(system_program::ID),
+                readonly(fixture.config.clone()),
+            ],
+        ),
+        &prism::instruction::CreateContextEncrypted {
+            context_type: 0,
+            max_per_transaction: MAX_PER_TRANSACTION,
+            reserved_space: 0,
+            root_identity_hash: hashv(&[prism::ROOT_IDENTITY_HASH_DOMAIN, fixture.root.key.as_ref()])
+                .to_bytes(),
+            encryption_commitment: commitment,
+            computation_id: None,
+            event_tag: None,
+            expires_at: None,
+            mint: None,
+            allowed_program: None,
+            label: [0; 32],
+            min_per_transaction: 0,
+        }
+        .data(),
+    );
+    let result = checked(result, &[&context]);
+    (context, result)
+}
+
+/// Send an owner-gated instruction taking [user, root_identity, context_identity, config]
+pub fn context_instruction(
+    fixture: &Fixture,
+    context: &AccountInfo<'static>,
+    instruction: impl InstructionData,
+) -> ProgramResult {
+    let result = process(
+        &prism::ID,
+        &with_cosigners(
+            fixture,
+            &[
+                fixture.owner.clone(),
+                fixture.root.clone(),
+                context.clone(),
+                readonly(fixture.config.clone()),
+            ],
+        ),
+        &instruction.data(),
+    );
+    checked(result, &[context])
+}
+
+pub fn set_verifier_key(fixture: &Fixture, context: &AccountInfo<'static>, verifier: Pubkey) {
+    assert_ok(context_instruction(
+        fixture,
+        context,
+        prism::instruction::SetVerifierKey { verifier_pubkey: Some(verifier) },
+    ));
+}
+
+/// Revoke `context`, cascading to the sub-contexts in `children`
+pub fn revoke_context(
+    fixture: &Fixture,
+    context: &AccountInfo<'static>,
+    children: &[AccountInfo<'static>],
+) -> ProgramResult {
+    let mut accounts = vec![fixture.owner.clone(), fixture.root.clone(), context.clone()];
+    accounts.extend_from_slice(children);
+    let result =
+        process(&prism::ID, &with_cosigners(fixture, &accounts), &prism::instruction::RevokeContext {}.data());
+    checked(result, &accounts[2..].iter().collect::<Vec<_>>())
+}
+
+pub fn close_revoked_context(fixture: &Fixture, context: &AccountInfo<'static>) -> ProgramResult {
+    context_instruction(fixture, context, prism::instruction::CloseRevokedContext {})
+}
+
+pub fn close_root_identity(fixture: &Fixture) -> ProgramResult {
+    process(
+        &prism::ID,
+        &with_cosigners(
+            fixture,
+            &[fixture.owner.clone(), fixture.root.clone(), readonly(fixture.config.clone())],
+        ),
+        &prism::instruction::CloseRootIdentity {}.data(),
+    )
+}
+
+pub fn create_sub_context(
+    fixture: &Fixture,
+    parent: &AccountInfo<'static>,
+) -> (AccountInfo<'static>, ProgramResult) {
+    let child_count = harness::data::<ContextIdentity>(parent).child_count;
+    let (sub_key, _) = Pubkey::find_program_address(
+        &[b"sub_context", parent.key.as_ref(), &child_count.to_le_bytes()],
+        &prism::ID,
+    );
+    let sub_context = empty(sub_key);
+    let result = process(
+        &prism::ID,
+        &with_cosigners(
+            fixture,
+            &[
+                fixture.owner.clone(),
+                fixture.root.clone(),
+                parent.clone(),
+                sub_context.clone(),
+                program(system_program::ID),
+                readonly(fixture.config.clone()),
+            ],
+        ),
+        &prism::instruction::CreateSubContext { context_type: 0, max_per_transaction: 1_000 }.data(),
+    );
+    let result = checked(result, &[parent, &sub_context]);
+    (sub_context, result)
+}
+
+/// Record a plain lamport spend by the fixture owner, optionally carrying hook data
+pub fn record_spending(
+    fixture: &Fixture,
+    context: &AccountInfo<'static>,
+    amount: u64,
+    hook_data: Option<[u8; 32]>,
+) -> ProgramResult {
+    context_instruction(
+        fixture,
+        context,
+        prism::instruction::RecordSpending {
+            amount,
+            auth_code: None,
+            expected_amount: None,
+            max_slippage_bps: None,
+            hook_data,
+            target_program: Pubkey::new_unique(),
+            nonce: 0,
+        },
+    )
+}
@@ -0,0 +1,338 @@
+//! In-process stand-in for the Solana runtime, enough to run Prism and the mock
+//! caller natively: syscall stubs for the clock, rent, return data and CPI, with
+//! the system program's account creation emulated. Accounts are laid out the way
+//! the loader serializes them so `AccountInfo::realloc` and `assign` work.
+
+#![allow(dead_code)]
+
+use std::cell::RefCell;
+use std::sync::Once;
+
+use anchor_lang::prelude::*;
+use anchor_lang::solana_program::entrypoint::{ProgramResult, MAX_PERMITTED_DATA_INCREASE};
+use anchor_lang::solana_program::instruction::Instruction;
+use anchor_lang::solana_program::program_stubs::{set_syscall_stubs, SyscallStubs};
+use anchor_lang::solana_program::sysvar::instructions::{
+    construct_instructions_data, store_current_index, BorrowedAccountMeta, BorrowedInstruction,
+};
+use anchor_lang::{system_program, AccountSerialize};
+
+pub const START_TIME: i64 = 1_700_000_000;
+pub const AIRDROP: u64 = 100_000_000_000;
+
+thread_local! {
+    static CLOCK: RefCell<Clock> = RefCell::new(Clock {
+        unix_timestamp: START_TIME,
+        ..Clock::default()
+    });
+    static PROGRAM_STACK: RefCell<Vec<Pubkey>> = const { RefCell::new(Vec::new()) };
+    static RETURN_DATA: RefCell<Option<(Pubkey, Vec<u8>)>> = const { RefCell::new(None) };
+    static LOGS: RefCell<Vec<String>> = const { RefCell::new(Vec::new()) };
+    static EVENTS: RefCell<Vec<Vec<u8>>> = const { RefCell::new(Vec::new()) };
+}
+
+/// Fields the runtime keeps next to the account data; `original_len` must sit
+/// right before `key`, where `AccountInfo::original_data_len` reads it
+#[repr(C)]
+struct AccountHeader {
+    _padding: u32,
+    original_len: u32,
+    key: Pubkey,
+    owner: Pubkey,
+    lamports: u64,
+}
+
+/// Allocate an account for the rest of the test. Data is preceded by its u64
+/// length and followed by the realloc headroom the loader reserves.
+pub fn account(key: Pubkey, owner: Pubkey, lamports: u64, data: &[u8]) -> AccountInfo<'static> {
+    let header = Box::leak(Box::new(AccountHeader {
+        _padding: 0,
+        original_len: data.len() as u32,
+        key,
+        owner,
+        lamports,
+    }));
+    let words = (8 + data.len() + MAX_PERMITTED_DATA_INCREASE).div_ceil(8);
+    let buffer: &'static mut [u64] = Box::leak(vec![0u64; words].into_boxed_slice());
+    buffer[0] = data.len() as u64;
+    let bytes = unsafe {
+        std::slice::from_raw_parts_mut((buffer.as_mut_ptr() as *mut u8).add(8), data.len())
+    };
+    bytes.copy_from_slice(data);
+
+    AccountInfo::new(
+        &header.key,
+        false,
+        true,
+        &mut header.lamports,
+        bytes,
+        &header.owner,
+        false,
+        0,
+    )
+}
+
+/// A funded wallet that signs the transaction
+pub fn wallet(key: Pubkey) -> AccountInfo<'static> {
+    let mut info = account(key, system_program::ID, AIRDROP, &[]);
+    info.is_signer = true;
+    info
+}
+
+/// An address nothing has been created at yet
+pub fn empty(key: Pubkey) -> AccountInfo<'static> {
+    account(key, system_program::ID, 0, &[])
+}
+
+/// A rent-exempt account owned by `program_id` holding `value`
+pub fn state<T: AccountSerialize>(
+    key: Pubkey,
+    program_id: Pubkey,
+    value: &T,
+    space: usize,
+) -> AccountInfo<'static> {
+    let mut data = Vec::with_capacity(space);
+    value.try_serialize(&mut data).unwrap();
+    data.resize(space, 0);
+    let lamports = Rent::default().minimum_balance(space);
+    account(key, program_id, lamports, &data)
+}
+
+/// An executable program account
+pub fn program(program_id: Pubkey) -> AccountInfo<'static> {
+    let mut info = account(program_id, Pubkey::default(), 1, &[]);
+    info.executable = true;
+    info.is_writable = false;
+    info
+}
+
+pub fn readonly(mut info: AccountInfo<'static>) -> AccountInfo<'static> {
+    info.is_writable = false;
+    info
+}
+
+/// The instructions sysvar for a transaction made of `instructions`, currently at `current`
+pub fn instructions_sysvar(instructions: &[Instruction], current: u16) -> AccountInfo<'static> {
+    let borrowed: Vec<BorrowedInstruction> = instructions
+        .iter()
+        .map(|ix| BorrowedInstruction {
+            program_id: &ix.program_id,
+            accounts: ix
+                .accounts
+                .iter()
+                .map(|meta| BorrowedAccountMeta {
+                    pubkey: &meta.pubkey,
+                    is_signer: meta.is_signer,
+                    is_writable: meta.is_writable,
+                })
+                .collect(),
+            data: &ix.data,
+        })
+        .collect();
+    let mut data = construct_instructions_data(&borrowed);
+    store_current_index(&mut data, current);
+
+    let mut info = account(
+        anchor_lang::solana_program::sysvar::instructions::ID,
+        anchor_lang::solana_program::sysvar::ID,
+        1,
+        &data,
+    );
+    info.is_writable = false;
+    info
+}
+
+pub fn set_time(unix_timestamp: i64) {
+    CLOCK.with(|clock| clock.borrow_mut().unix_timestamp = unix_timestamp);
+}
+
+pub fn set_epoch(epoch: u64) {
+    CLOCK.with(|clock| clock.borrow_mut().epoch = epoch);
+}
+
+pub fn return_data() -> Option<(Pubkey, Vec<u8>)> {
+    RETURN_DATA.with(|data| data.borrow().clone())
+}
+
+/// Program logs of the last top-level instruction
+pub fn logs() -> Vec<String> {
+    LOGS.with(|logs| logs.borrow().clone())
+}
+
+/// Events of type `T` emitted by the last top-level instruction, in order
+pub fn events<T: anchor_lang::Event>() -> Vec<T> {
+    EVENTS.with(|events| {
+        events
+            .borrow()
+            .iter()
+            .filter_map(|data| data.strip_prefix(T::DISCRIMINATOR))
+            .map(|mut payload| T::deserialize(&mut payload).unwrap())
+            .collect()
+    })
+}
+
+/// Unwrap an instruction result, showing the program logs if it failed
+#[track_caller]
+pub fn assert_ok(result: ProgramResult) {
+    if let Err(err) = result {
+        panic!("instruction failed: {err:?}\n{}", logs().join("\n"));
+    }
+}
+
+/// Run a top-level instruction of `program_id` over `accounts`
+pub fn process(program_id: &Pubkey, accounts: &[AccountInfo<'static>], data: &[u8]) -> ProgramResult {
+    install_stubs();
+    RETURN_DATA.with(|data| *data.borrow_mut() = None);
+    LOGS.with(|logs| logs.borrow_mut().clear());
+    EVENTS.with(|events| events.borrow_mut().clear());
+    let accounts: &'static [AccountInfo<'static>] = Box::leak(accounts.to_vec().into_boxed_slice());
+    dispatch(program_id, accounts, data)
+}
+
+pub fn data<T: AnchorDeserialize>(info: &AccountInfo) -> T {
+    let data = info.try_borrow_data().unwrap();
+    T::deserialize(&mut &data[8..]).unwrap()
+}
+
+fn dispatch<'a>(program_id: &Pubkey, accounts: &'a [AccountInfo<'a>], data: &[u8]) -> ProgramResult {
+    PROGRAM_STACK.with(|stack| stack.borrow_mut().push(*program_id));
+    let result = if *program_id == prism::ID {
+        prism::entry(program_id, accounts, data)
+    } else if *program_id == mock_caller::ID {
+        mock_caller::entry(program_id, accounts, data)
+    } else if *program_id == system_program::ID {
+        system_instruction(accounts, data)
+    } else {
+        Err(ProgramError::IncorrectProgramId)
+    };
+    PROGRAM_STACK.with(|stack| stack.borrow_mut().pop());
+    result
+}
+
+fn current_program() -> Pubkey {
+    PROGRAM_STACK.with(|stack| *stack.borrow().last().unwrap())
+}
+
+fn install_stubs() {
+    static STUBS: Once = Once::new();
+    STUBS.call_once(|| {
+        set_syscall_stubs(Box::new(TestStubs));
+    });
+}
+
+struct TestStubs;
+
+impl SyscallStubs for TestStubs {
+    /// Kept for assert_ok to show when an instruction fails
+    fn sol_log(&self, message: &str) {
+        LOGS.with(|logs| logs.borrow_mut().push(message.to_string()));
+    }
+
+    fn sol_log_data(&self, fields: &[&[u8]]) {
+        EVENTS.with(|events| events.borrow_mut().push(fields.concat()));
+    }
+
+    fn sol_get_clock_sysvar(&self, var_addr: *mut u8) -> u64 {
+        CLOCK.with(|clock| unsafe { *(var_addr as *mut Clock) = clock.borrow().clone() });
+        0
+    }
+
+    fn sol_get_rent_sysvar(&self, var_addr: *mut u8) -> u64 {
+        unsafe { *(var_addr as *mut Rent) = Rent::default() };
+        0
+    }
+
+    fn sol_get_return_data(&self) -> Option<(Pubkey, Vec<u8>)> {
+        return_data()
+    }
+
+    fn sol_set_return_data(&self, data: &[u8]) {
+        let program_id = current_program();
+        RETURN_DATA.with(|stored| *stored.borrow_mut() = Some((program_id, data.to_vec())));
+    }
+
+    fn sol_invoke_signed(
+        &self,
+        instruction: &Instruction,
+        account_infos: &[AccountInfo],
+        signers_seeds: &[&[&[u8]]],
+    ) -> ProgramResult {
+        let caller = current_program();
+        let pda_signers: Vec<Pubkey> = signers_seeds
+            .iter()
+            .map(|seeds| Pubkey::create_program_address(seeds, &caller).unwrap())
+            .collect();
+
+        let mut accounts = Vec::with_capacity(instruction.accounts.len());
+        for meta in &instruction.accounts {
+            let mut info = account_infos
+                .iter()
+                .find(|info| *info.key == meta.pubkey)
+                .ok_or(ProgramError::NotEnoughAccountKeys)?
+                .clone();
+            if meta.is_signer && !info.is_signer && !pda_signers.contains(&meta.pubkey) {
+                return Err(ProgramError::MissingRequiredSignature);
+            }
+            if meta.is_writable && !info.is_writable {
+                return Err(ProgramError::InvalidArgument);
+            }
+            info.is_signer = meta.is_signer;
+            info.is_writable = meta.is_writable;
+            accounts.push(info);
+        }
+
+        RETURN_DATA.with(|data| *data.borrow_mut() = None);
+        let accounts = Box::leak(accounts.into_boxed_slice());
+        dispatch(&instruction.program_id, accounts, &instruction.data)
+    }
+}
+
+/// The subset of the system program Anchor's `init` and Prism rely on
+fn system_instruction(accounts: &[AccountInfo], data: &[u8]) -> ProgramResult {
+    let read_u64 = |at: usize| u64::from_le_bytes(data[at..at + 8].try_into().unwrap());
+    let read_key = |at: usize| Pubkey::try_from(&data[at..at + 32]).unwrap();
+
+    match u32::from_le_bytes(data[..4].try_into().unwrap()) {
+        // CreateAccount { lamports, space, owner }
+        0 => {
+            let (from, to) = (&accounts[0], &accounts[1]);
+            if to.lamports() != 0 || !to.data_is_empty() {
+                return Err(ProgramError::AccountAlreadyInitialized);
+            }
+            move_lamports(from, to, read_u64(4))?;
+            set_data_len(to, read_u64(12) as usize);
+            to.assign(&read_key(20));
+        }
+        // Assign { owner }
+        1 => accounts[0].assign(&read_key(4)),
+        // Transfer { lamports }
+        2 => move_lamports(&accounts[0], &accounts[1], read_u64(4))?,
+        // Allocate { space }
+        8 => set_data_len(&accounts[0], read_u64(4) as usize),
+        _ => return Err(ProgramError::InvalidInstructionData),
+    }
+
+    Ok(())
+}
+
+fn move_lamports(from: &AccountInfo, to: &AccountInfo, lamports: u64) -> ProgramResult {
+    if !from.is_signer {
+        return Err(ProgramError::MissingRequiredSignature);
+    }
+    let mut from_lamports = from.try_borrow_mut_lamports()?;
+    **from_lamports = from_lamports
+        .checked_sub(lamports)
+        .ok_or(ProgramError::InsufficientFunds)?;
+    **to.try_borrow_mut_lamports()? += lamports;
+    Ok(())
+}
+
+fn set_data_len(info: &AccountInfo, len: usize) {
+    let mut data = info.data.borrow_mut();
+    unsafe {
+        let data_ptr = data.as_mut_ptr();
+        *(data_ptr.offset(-8) as *mut u64) = len as u64;
+        *data = std::slice::from_raw_parts_mut(data_ptr, len);
+    }
+    data.fill(0);
+}
@@ -0,0 +1,80 @@
+//! Revoking contexts on a root controlled by several owners
+
+mod fixtures;
+mod harness;
+
+use anchor_lang::prelude::*;
+use anchor_lang::solana_program::entrypoint::ProgramResult;
+use anchor_lang::InstructionData;
+use fixtures::{
+    checked, create_context, create_sub_context, prism_error, revoke_context, setup_multisig,
+    with_cosigners,
+};
+use harness::{assert_ok, process};
+use prism::{ContextIdentity, PrismError, RootIdentity};
+
+fn revoke_all_contexts(accounts: &[AccountInfo<'static>]) -> ProgramResult {
+    let result = process(
+        &prism::ID,
+        accounts,
+        &prism::instruction::RevokeAllContexts { fail_on_already_revoked: false }.data(),
+    );
+    // Co-signers are wallets, not contexts
+    let contexts: Vec<_> = accounts[2..].iter().filter(|account| *account.owner == prism::ID).collect();
+    checked(result, &contexts)
+}
+
+#[test]
+fn revoke_context_with_cosigners_cascades_to_sub_contexts() {
+    let fixture = setup_multisig(2, 2);
+    let (parent, result) = create_context(&fixture, 0);
+    assert_ok(result);
+    let (sub_context, result) = create_sub_context(&fixture, &parent);
+    assert_ok(result);
+
+    // Co-signers and the sub-context share remaining_accounts
+    assert_ok(revoke_context(&fixture, &parent, std::slice::from_ref(&sub_context)));
+
+    assert!(harness::data::<ContextIdentity>(&parent).revoked);
+    assert!(harness::data::<ContextIdentity>(&sub_context).revoked);
+}
+
+#[test]
+fn revoke_context_needs_a_quorum() {
+    let fixture = setup_multisig(2, 2);
+    let (context, result) = create_context(&fixture, 0);
+    assert_ok(result);
+
+    let alone = process(
+        &prism::ID,
+        &[fixture.owner.clone(), fixture.root.clone(), context.clone()],
+        &prism::instruction::RevokeContext {}.data(),
+    );
+
+    assert_eq!(alone, Err(prism_error(PrismError::MultisigThresholdNotMet)));
+    assert!(!harness::data::<ContextIdentity>(&context).revoked);
+}
+
+#[test]
+fn revoke_all_contexts_needs_a_quorum() {
+    let fixture = setup_multisig(2, 2);
+    let mut contexts = Vec::new();
+    for index in 0..2 {
+        let (context, result) = create_context(&fixture, index);
+        assert_ok(result);
+        contexts.push(context);
+    }
+    let mut accounts = vec![fixture.owner.clone(), fixture.root.clone()];
+    accounts.extend_from_slice(&contexts);
+
+    assert_eq!(
+        revoke_all_contexts(&accounts),
+        Err(prism_error(PrismError::MultisigThresholdNotMet))
+    );
+
+    assert_ok(revoke_all_contexts(&with_cosigners(&fixture, &accounts)));
+    for context in &contexts {
+        assert!(harness::data::<ContextIdentity>(context).revoked);
+    }
+    assert_eq!(harness::data::<RootIdentity>(&fixture.root).revoked_count, 2);
+}
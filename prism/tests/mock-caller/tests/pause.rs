@@ -0,0 +1,54 @@
+//! The program-wide circuit breaker held by the config admin
+
+mod fixtures;
+mod harness;
+
+use anchor_lang::prelude::*;
+use anchor_lang::solana_program::entrypoint::ProgramResult;
+use anchor_lang::InstructionData;
+use fixtures::{create_context, prism_error, record_spending, revoke_context, setup, Fixture};
+use harness::{assert_ok, process, wallet};
+use prism::{ContextIdentity, PrismError, ProgramConfig};
+
+fn set_paused(fixture: &Fixture, admin: &AccountInfo<'static>, paused: bool) -> ProgramResult {
+    let accounts = [admin.clone(), fixture.config.clone()];
+    if paused {
+        process(&prism::ID, &accounts, &prism::instruction::Pause {}.data())
+    } else {
+        process(&prism::ID, &accounts, &prism::instruction::Unpause {}.data())
+    }
+}
+
+#[test]
+fn pause_halts_spending_and_creation_but_not_revocation() {
+    let fixture = setup();
+    let (spent, result) = create_context(&fixture, 0);
+    assert_ok(result);
+    let (revoked, result) = create_context(&fixture, 1);
+    assert_ok(result);
+
+    assert_ok(set_paused(&fixture, &fixture.owner, true));
+    assert!(harness::data::<ProgramConfig>(&fixture.config).paused);
+
+    assert_eq!(record_spending(&fixture, &spent, 100, None), Err(prism_error(PrismError::ProgramPaused)));
+    let (_, result) = create_context(&fixture, 2);
+    assert_eq!(result, Err(prism_error(PrismError::ProgramPaused)));
+    assert_ok(revoke_context(&fixture, &revoked, &[]));
+
+    assert_ok(set_paused(&fixture, &fixture.owner, false));
+    assert_ok(record_spending(&fixture, &spent, 100, None));
+    assert_eq!(harness::data::<ContextIdentity>(&spent).total_spent, 100);
+}
+
+#[test]
+fn only_the_admin_can_pause() {
+    let fixture = setup();
+    let stranger = wallet(Pubkey::new_unique());
+
+    assert_eq!(set_paused(&fixture, &stranger, true), Err(prism_error(PrismError::Unauthorized)));
+    assert_eq!(set_paused(&fixture, &fixture.owner, false), Err(prism_error(PrismError::ProgramNotPaused)));
+
+    assert_ok(set_paused(&fixture, &fixture.owner, true));
+    assert_eq!(set_paused(&fixture, &stranger, false), Err(prism_error(PrismError::Unauthorized)));
+    assert_eq!(set_paused(&fixture, &fixture.owner, true), Err(prism_error(PrismError::ProgramPaused)));
+}
@@ -0,0 +1,132 @@
+//! Handing a root to a new owner through its recovery guardians
+
+mod fixtures;
+mod harness;
+
+use anchor_lang::prelude::*;
+use anchor_lang::solana_program::entrypoint::ProgramResult;
+use anchor_lang::InstructionData;
+use fixtures::{prism_error, setup, with_cosigners, Fixture};
+use harness::{assert_ok, process, readonly, wallet, START_TIME};
+use prism::{PrismError, RootIdentity, RECOVERY_TIMELOCK};
+
+/// A root with two of its three guardian slots filled, both needed to recover
+fn guarded_root() -> (Fixture, [AccountInfo<'static>; 2]) {
+    let fixture = setup();
+    let guardians = [wallet(Pubkey::new_unique()), wallet(Pubkey::new_unique())];
+    assert_ok(set_guardians(
+        &fixture,
+        [*guardians[0].key, *guardians[1].key, Pubkey::default()],
+        2,
+    ));
+    (fixture, guardians)
+}
+
+fn set_guardians(fixture: &Fixture, guardians: [Pubkey; 3], recovery_threshold: u8) -> ProgramResult {
+    process(
+        &prism::ID,
+        &with_cosigners(
+            fixture,
+            &[fixture.owner.clone(), fixture.root.clone(), readonly(fixture.config.clone())],
+        ),
+        &prism::instruction::SetGuardians { guardians, recovery_threshold }.data(),
+    )
+}
+
+/// Send a root-level instruction signed by `signer`, who needn't be an owner
+fn signed_by(
+    fixture: &Fixture,
+    signer: &AccountInfo<'static>,
+    instruction: impl InstructionData,
+) -> ProgramResult {
+    process(
+        &prism::ID,
+        &[signer.clone(), fixture.root.clone(), readonly(fixture.config.clone())],
+        &instruction.data(),
+    )
+}
+
+#[test]
+fn guardians_recover_the_root_after_the_timelock() {
+    let (fixture, guardians) = guarded_root();
+    let new_owner = Pubkey::new_unique();
+    let stranger = wallet(Pubkey::new_unique());
+
+    assert_ok(signed_by(&fixture, &guardians[0], prism::instruction::InitiateRecovery { new_owner }));
+    assert_eq!(
+        signed_by(&fixture, &stranger, prism::instruction::ExecuteRecovery {}),
+        Err(prism_error(PrismError::RecoveryThresholdNotMet))
+    );
+    assert_eq!(
+        signed_by(&fixture, &stranger, prism::instruction::ApproveRecovery {}),
+        Err(prism_error(PrismError::NotAGuardian))
+    );
+
+    assert_ok(signed_by(&fixture, &guardians[1], prism::instruction::ApproveRecovery {}));
+    assert_eq!(
+        signed_by(&fixture, &stranger, prism::instruction::ExecuteRecovery {}),
+        Err(prism_error(PrismError::RecoveryTimelockActive))
+    );
+
+    // Anyone can execute an approved recovery once the timelock has passed
+    harness::set_time(START_TIME + RECOVERY_TIMELOCK);
+    assert_ok(signed_by(&fixture, &stranger, prism::instruction::ExecuteRecovery {}));
+
+    let root: RootIdentity = harness::data(&fixture.root);
+    assert_eq!(root.owner, new_owner);
+    assert_eq!(root.recovery_new_owner, None);
+    assert_eq!(root.recovery_approvals, 0);
+}
+
+#[test]
+fn owner_can_cancel_a_pending_recovery() {
+    let (fixture, guardians) = guarded_root();
+    let new_owner = Pubkey::new_unique();
+    assert_ok(signed_by(&fixture, &guardians[0], prism::instruction::InitiateRecovery { new_owner }));
+    assert_ok(signed_by(&fixture, &guardians[1], prism::instruction::ApproveRecovery {}));
+
+    assert_ok(process(
+        &prism::ID,
+        &[fixture.owner.clone(), fixture.root.clone()],
+        &prism::instruction::CancelRecovery {}.data(),
+    ));
+
+    harness::set_time(START_TIME + RECOVERY_TIMELOCK);
+    assert_eq!(
+        signed_by(&fixture, &guardians[0], prism::instruction::ExecuteRecovery {}),
+        Err(prism_error(PrismError::NoRecoveryPending))
+    );
+    assert_eq!(harness::data::<RootIdentity>(&fixture.root).owner, *fixture.owner.key);
+}
+
+#[test]
+fn recovery_needs_guardians_configured() {
+    let fixture = setup();
+    let guardian = wallet(Pubkey::new_unique());
+
+    assert_eq!(
+        signed_by(
+            &fixture,
+            &guardian,
+            prism::instruction::InitiateRecovery { new_owner: Pubkey::new_unique() }
+        ),
+        Err(prism_error(PrismError::RecoveryNotConfigured))
+    );
+}
+
+#[test]
+fn guardians_must_be_distinct_non_owners() {
+    let fixture = setup();
+    let guardian = Pubkey::new_unique();
+
+    for (guardians, threshold) in [
+        ([guardian, guardian, Pubkey::default()], 1),
+        ([*fixture.owner.key, guardian, Pubkey::default()], 1),
+        ([guardian, Pubkey::default(), Pubkey::default()], 2),
+    ] {
+        assert_eq!(
+            set_guardians(&fixture, guardians, threshold),
+            Err(prism_error(PrismError::InvalidGuardianConfig))
+        );
+    }
+}
@@ -0,0 +1,119 @@
+//! What Prism hands back to clients as return data, decoded the way a client
+//! reading a simulated or confirmed transaction would
+
+mod fixtures;
+mod harness;
+
+use anchor_lang::prelude::*;
+use anchor_lang::solana_program::hash::hashv;
+use anchor_lang::InstructionData;
+use fixtures::{create_context, create_encrypted_context, record_spending, setup, Fixture};
+use harness::{assert_ok, readonly};
+use prism::{CreatedContext, ProgramInfo, CONTEXT_LEAF_DOMAIN, CONTEXT_NODE_DOMAIN};
+
+/// Prism's return data from the last instruction, decoded as `T`
+fn returned<T: AnchorDeserialize>() -> T {
+    let (program_id, data) = harness::return_data().expect("no return data");
+    assert_eq!(program_id, prism::ID);
+    T::try_from_slice(&data).unwrap()
+}
+
+/// Interior node of the contexts Merkle tree: children hashed in ascending order
+fn merkle_node(a: [u8; 32], b: [u8; 32]) -> [u8; 32] {
+    let (left, right) = if a <= b { (a, b) } else { (b, a) };
+    hashv(&[CONTEXT_NODE_DOMAIN, &left, &right]).to_bytes()
+}
+
+fn merkle_leaf(context: &Pubkey) -> [u8; 32] {
+    hashv(&[CONTEXT_LEAF_DOMAIN, context.as_ref()]).to_bytes()
+}
+
+fn verify_context_inclusion(fixture: &Fixture, context: Pubkey, proof: Vec<[u8; 32]>) -> bool {
+    assert_ok(harness::process(
+        &prism::ID,
+        &[readonly(fixture.root.clone())],
+        &prism::instruction::VerifyContextInclusion { context, proof }.data(),
+    ));
+    returned()
+}
+
+#[test]
+fn create_context_returns_address_and_index() {
+    let fixture = setup();
+
+    for index in 0..2 {
+        let (context, result) = create_context(&fixture, index);
+        assert_ok(result);
+
+        let created: CreatedContext = returned();
+        assert_eq!(created.context_identity, *context.key);
+        assert_eq!(created.context_index, index);
+    }
+}
+
+#[test]
+fn create_context_encrypted_returns_address_and_index() {
+    let fixture = setup();
+    let (_, result) = create_context(&fixture, 0);
+    assert_ok(result);
+
+    let (context, result) = create_encrypted_context(&fixture, [7; 32], 1);
+    assert_ok(result);
+
+    let created: CreatedContext = returned();
+    assert_eq!(created.context_identity, *context.key);
+    assert_eq!(created.context_index, 1);
+}
+
+#[test]
+fn get_program_info_reports_versions() {
+    assert_ok(harness::process(
+        &prism::ID,
+        &[harness::program(prism::ID)],
+        &prism::instruction::GetProgramInfo {}.data(),
+    ));
+
+    let info: ProgramInfo = returned();
+    assert_eq!(info.program_id, prism::ID);
+    assert_eq!(info.version, prism::PROGRAM_VERSION);
+    assert_eq!(info.root_identity_version, prism::ROOT_IDENTITY_VERSION);
+    assert_eq!(info.context_identity_version, prism::CONTEXT_IDENTITY_VERSION);
+}
+
+#[test]
+fn validate_state_consistency_reports_no_violations() {
+    let fixture = setup();
+    let (context, result) = create_context(&fixture, 0);
+    assert_ok(result);
+    assert_ok(record_spending(&fixture, &context, 100, None));
+
+    assert_ok(harness::process(
+        &prism::ID,
+        &[readonly(context)],
+        &prism::instruction::ValidateStateConsistency {}.data(),
+    ));
+    assert_eq!(returned::<u32>(), 0);
+}
+
+#[test]
+fn verify_context_inclusion_checks_the_published_root() {
+    let fixture = setup();
+    let [a, b, c] = [Pubkey::new_unique(), Pubkey::new_unique(), Pubkey::new_unique()];
+    let ab = merkle_node(merkle_leaf(&a), merkle_leaf(&b));
+    // An odd leaf is paired with itself
+    let cc = merkle_node(merkle_leaf(&c), merkle_leaf(&c));
+
+    // Nothing is included before a root is published
+    assert!(!verify_context_inclusion(&fixture, a, vec![merkle_leaf(&b), cc]));
+
+    assert_ok(harness::process(
+        &prism::ID,
+        &[fixture.owner.clone(), fixture.root.clone(), readonly(fixture.config.clone())],
+        &prism::instruction::UpdateContextsMerkleRoot { merkle_root: merkle_node(ab, cc) }.data(),
+    ));
+
+    assert!(verify_context_inclusion(&fixture, a, vec![merkle_leaf(&b), cc]));
+    assert!(verify_context_inclusion(&fixture, c, vec![merkle_leaf(&c), ab]));
+    assert!(!verify_context_inclusion(&fixture, Pubkey::new_unique(), vec![merkle_leaf(&b), cc]));
+    assert!(!verify_context_inclusion(&fixture, a, vec![cc, merkle_leaf(&b)]));
+}
@@ -0,0 +1,199 @@
+//! Closing a root identity and creating it again from the same wallet
+
+mod fixtures;
+mod harness;
+
+use anchor_lang::prelude::*;
+use anchor_lang::solana_program::entrypoint::ProgramResult;
+use anchor_lang::{system_program, InstructionData};
+use fixtures::{
+    close_revoked_context, close_root_identity, create_context, create_root_identity,
+    create_sub_context, prism_error, revoke_context, setup, Fixture,
+};
+use harness::{assert_ok, empty, process, program, readonly};
+use prism::{ContextIdentity, PrismError, RootIdentity};
+
+fn revoke_and_archive(fixture: &Fixture, context: &AccountInfo<'static>) -> ProgramResult {
+    let state: ContextIdentity = harness::data(context);
+    let (archive_key, _) = Pubkey::find_program_address(
+        &[
+            b"archive",
+            fixture.root.key.as_ref(),
+            &state.context_index.to_le_bytes(),
+            &state.created_at.to_le_bytes(),
+        ],
+        &prism::ID,
+    );
+    process(
+        &prism::ID,
+        &[
+            fixture.owner.clone(),
+            fixture.root.clone(),
+            context.clone(),
+            empty(archive_key),
+            program(system_program::ID),
+            readonly(fixture.config.clone()),
+        ],
+        &prism::instruction::RevokeAndClose { archive_on_close: true }.data(),
+    )
+}
+
+/// The fixture's root as a v8 account, from before open_contexts existed
+fn v8_root(fixture: &Fixture) -> AccountInfo<'static> {
+    let mut data = fixture.root.try_borrow_data().unwrap().to_vec();
+    data[8] = 8;
+    data.truncate(RootIdentity::SIZE - 4);
+    harness::account(*fixture.root.key, prism::ID, fixture.root.lamports(), &data)
+}
+
+fn migrate_root(
+    fixture: &Fixture,
+    root: &AccountInfo<'static>,
+    open_contexts: &[AccountInfo<'static>],
+) -> ProgramResult {
+    let mut accounts = vec![
+        fixture.owner.clone(),
+        fixture.owner.clone(),
+        root.clone(),
+        // No root_identity: Anchor reads the program id as None
+        program(prism::ID),
+        program(system_program::ID),
+        readonly(fixture.config.clone()),
+    ];
+    accounts.extend_from_slice(open_contexts);
+    process(&prism::ID, &accounts, &prism::instruction::MigrateAccount {}.data())
+}
+
+fn is_closed(account: &AccountInfo) -> bool {
+    account.lamports() == 0 && account.data_is_empty() && *account.owner == system_program::ID
+}
+
+#[test]
+fn close_root_waits_for_revoked_contexts_to_be_closed() {
+    let fixture = setup();
+    let (context, result) = create_context(&fixture, 0);
+    assert_ok(result);
+    assert_ok(revoke_context(&fixture, &context, &[]));
+
+    // Revoked but still open: its address would collide with a re-created root's first context
+    assert_eq!(close_root_identity(&fixture), Err(prism_error(PrismError::ActiveContextsRemain)));
+
+    assert_ok(close_revoked_context(&fixture, &context));
+    assert!(is_closed(&context));
+    assert_ok(close_root_identity(&fixture));
+    assert!(is_closed(&fixture.root));
+}
+
+#[test]
+fn close_revoked_context_rejects_live_contexts() {
+    let fixture = setup();
+    let (context, result) = create_context(&fixture, 0);
+    assert_ok(result);
+
+    assert_eq!(
+        close_revoked_context(&fixture, &context),
+        Err(prism_error(PrismError::ContextNotRevoked))
+    );
+}
+
+#[test]
+fn sub_contexts_keep_the_root_open() {
+    let fixture = setup();
+    let (parent, result) = create_context(&fixture, 0);
+    assert_ok(result);
+    let (sub_context, result) = create_sub_context(&fixture, &parent);
+    assert_ok(result);
+    assert_eq!(harness::data::<RootIdentity>(&fixture.root).open_contexts, 2);
+
+    assert_ok(revoke_context(&fixture, &parent, std::slice::from_ref(&sub_context)));
+    assert_ok(close_revoked_context(&fixture, &parent));
+    assert_eq!(close_root_identity(&fixture), Err(prism_error(PrismError::ActiveContextsRemain)));
+
+    assert_ok(close_revoked_context(&fixture, &sub_context));
+    assert_ok(close_root_identity(&fixture));
+}
+
+#[test]
+fn recreated_root_starts_over_at_index_zero() {
+    let fixture = setup();
+    for index in 0..2 {
+        let (context, result) = create_context(&fixture, index);
+        assert_ok(result);
+        assert_ok(revoke_context(&fixture, &context, &[]));
+        assert_ok(close_revoked_context(&fixture, &context));
+    }
+    assert_ok(close_root_identity(&fixture));
+
+    assert_ok(create_root_identity(&fixture));
+    let root: RootIdentity = harness::data(&fixture.root);
+    assert_eq!(root.context_count, 0);
+    assert_eq!(root.revoked_count, 0);
+    assert_eq!(root.open_contexts, 0);
+
+    // The first index is free again, and the new context starts from a clean slate
+    let (context, result) = create_context(&fixture, 0);
+    assert_ok(result);
+    let context: ContextIdentity = harness::data(&context);
+    assert!(!context.revoked);
+    assert_eq!(context.context_index, 0);
+    assert_eq!(context.total_spent, 0);
+}
+
+#[test]
+fn recreated_root_can_archive_at_reused_indexes() {
+    let fixture = setup();
+    let (context, result) = create_context(&fixture, 0);
+    assert_ok(result);
+    assert_ok(revoke_and_archive(&fixture, &context));
+    assert_ok(close_root_identity(&fixture));
+
+    harness::set_time(harness::START_TIME + 60);
+    assert_ok(create_root_identity(&fixture));
+    let (context, result) = create_context(&fixture, 0);
+    assert_ok(result);
+    assert_ok(revoke_and_archive(&fixture, &context));
+}
+
+#[test]
+fn migration_counts_only_contexts_still_open() {
+    let fixture = setup();
+    let mut contexts = Vec::new();
+    for index in 0..3 {
+        let (context, result) = create_context(&fixture, index);
+        assert_ok(result);
+        contexts.push(context);
+    }
+    let (sub_context, result) = create_sub_context(&fixture, &contexts[0]);
+    assert_ok(result);
+    assert_ok(revoke_context(&fixture, &contexts[1], &[]));
+    assert_ok(close_revoked_context(&fixture, &contexts[1]));
+    let open = [contexts[0].clone(), contexts[2].clone(), sub_context];
+
+    let root = v8_root(&fixture);
+    assert_ok(migrate_root(&fixture, &root, &open));
+
+    let migrated: RootIdentity = harness::data(&root);
+    assert_eq!(migrated.version, prism::ROOT_IDENTITY_VERSION);
+    assert_eq!(migrated.context_count, 3);
+    assert_eq!(migrated.open_contexts, 3);
+}
+
+#[test]
+fn migration_rejects_duplicate_or_foreign_contexts() {
+    let fixture = setup();
+    let (context, result) = create_context(&fixture, 0);
+    assert_ok(result);
+    let other = setup();
+    let (foreign, result) = create_context(&other, 0);
+    assert_ok(result);
+
+    let root = v8_root(&fixture);
+    assert_eq!(
+        migrate_root(&fixture, &root, &[context.clone(), context.clone()]),
+        Err(prism_error(PrismError::ContextMismatch))
+    );
+    assert_eq!(
+        migrate_root(&fixture, &root, &[foreign]),
+        Err(prism_error(PrismError::ContextMismatch))
+    );
+}
@@ -0,0 +1,196 @@
+//! Recording spends against a context, as a client of Prism would
+
+mod fixtures;
+mod harness;
+
+use anchor_lang::InstructionData;
+use anchor_lang::prelude::*;
+use anchor_lang::solana_program::entrypoint::ProgramResult;
+use fixtures::{context_instruction, create_context, prism_error, record_spending, setup, Fixture};
+use harness::{assert_ok, process, readonly, wallet};
+use prism::{ContextIdentity, PrismError, RootIdentity, SpendHook, SpendingRecorded};
+
+fn set_verbose_events(fixture: &Fixture, enabled: bool) {
+    assert_ok(process(
+        &prism::ID,
+        &[fixture.owner.clone(), fixture.config.clone()],
+        &prism::instruction::SetVerboseEvents { enabled }.data(),
+    ));
+}
+
+fn refund(fixture: &Fixture, context: &AccountInfo<'static>, amount: u64) -> ProgramResult {
+    context_instruction(fixture, context, prism::instruction::RefundSpending { amount })
+}
+
+/// Record a spend signed by `signer` rather than the fixture owner
+fn record_spending_as(
+    signer: &AccountInfo<'static>,
+    fixture: &Fixture,
+    context: &AccountInfo<'static>,
+) -> ProgramResult {
+    process(
+        &prism::ID,
+        &[signer.clone(), fixture.root.clone(), context.clone(), readonly(fixture.config.clone())],
+        &prism::instruction::RecordSpending {
+            amount: 100,
+            auth_code: None,
+            expected_amount: None,
+            max_slippage_bps: None,
+            hook_data: None,
+            target_program: Pubkey::new_unique(),
+            nonce: 0,
+        }
+        .data(),
+    )
+}
+
+/// A fresh context that has already spent `spent`
+fn spent_context(fixture: &Fixture, spent: u64) -> AccountInfo<'static> {
+    let (context, result) = create_context(fixture, 0);
+    assert_ok(result);
+    assert_ok(record_spending(fixture, &context, spent, None));
+    context
+}
+
+#[test]
+fn spend_hook_needs_verbose_events() {
+    let fixture = setup();
+    let (context, result) = create_context(&fixture, 0);
+    assert_ok(result);
+    let hook_data = [9; 32];
+
+    assert_ok(record_spending(&fixture, &context, 100, Some(hook_data)));
+    assert_eq!(harness::events::<SpendingRecorded>().len(), 1);
+    assert!(harness::events::<SpendHook>().is_empty());
+
+    set_verbose_events(&fixture, true);
+    assert_ok(record_spending(&fixture, &context, 100, Some(hook_data)));
+    let hooks = harness::events::<SpendHook>();
+    assert_eq!(hooks.len(), 1);
+    assert_eq!(hooks[0].context_identity, *context.key);
+    assert_eq!(hooks[0].amount, 100);
+    assert_eq!(hooks[0].hook_data, hook_data);
+
+    // Verbose events don't invent a hook the caller didn't ask for
+    assert_ok(record_spending(&fixture, &context, 100, None));
+    assert!(harness::events::<SpendHook>().is_empty());
+}
+
+#[test]
+fn refund_gives_back_budget_and_the_spend() {
+    let fixture = setup();
+    let context = spent_context(&fixture, 300);
+
+    assert_ok(refund(&fixture, &context, 100));
+
+    let state: ContextIdentity = harness::data(&context);
+    assert_eq!(state.total_spent, 200);
+    assert_eq!(state.spend_count, 0);
+    assert_eq!(harness::data::<RootIdentity>(&fixture.root).global_spent, 200);
+}
+
+#[test]
+fn refund_cannot_exceed_what_was_spent() {
+    let fixture = setup();
+    let context = spent_context(&fixture, 300);
+
+    assert_eq!(refund(&fixture, &context, 301), Err(prism_error(PrismError::RefundUnderflow)));
+    assert_eq!(refund(&fixture, &context, 0), Err(prism_error(PrismError::ZeroRefund)));
+    assert_eq!(harness::data::<ContextIdentity>(&context).total_spent, 300);
+}
+
+#[test]
+fn refund_rejects_sealed_contexts() {
+    let fixture = setup();
+    let context = spent_context(&fixture, 300);
+    assert_ok(context_instruction(&fixture, &context, prism::instruction::SealContext {}));
+
+    assert_eq!(refund(&fixture, &context, 100), Err(prism_error(PrismError::ContextSealed)));
+}
+
+#[test]
+fn refund_rejects_frozen_roots() {
+    let fixture = setup();
+    let context = spent_context(&fixture, 300);
+    assert_ok(process(
+        &prism::ID,
+        &[fixture.owner.clone(), fixture.root.clone()],
+        &prism::instruction::FreezeRoot {}.data(),
+    ));
+
+    assert_eq!(refund(&fixture, &context, 100), Err(prism_error(PrismError::RootFrozen)));
+}
+
+#[test]
+fn refund_is_capped_at_the_current_window() {
+    let fixture = setup();
+    let (context, result) = create_context(&fixture, 0);
+    assert_ok(result);
+    assert_ok(context_instruction(
+        &fixture,
+        &context,
+        prism::instruction::SetWindowLimit { window_duration: 60, max_per_window: 1_000 },
+    ));
+    assert_ok(record_spending(&fixture, &context, 500, None));
+
+    assert_eq!(
+        refund(&fixture, &context, 501),
+        Err(prism_error(PrismError::RefundExceedsCurrentSpend))
+    );
+    assert_ok(refund(&fixture, &context, 200));
+    assert_eq!(harness::data::<ContextIdentity>(&context).window_spent, 300);
+
+    // Once the window lapses its budget is back anyway; refunding would hand out extra
+    harness::set_time(harness::START_TIME + 60);
+    assert_eq!(
+        refund(&fixture, &context, 100),
+        Err(prism_error(PrismError::RefundExceedsCurrentSpend))
+    );
+}
+
+#[test]
+fn epoch_limit_resets_with_the_epoch() {
+    let fixture = setup();
+    let (context, result) = create_context(&fixture, 0);
+    assert_ok(result);
+    assert_ok(context_instruction(&fixture, &context, prism::instruction::SetEpochLimit { max_per_epoch: 1_000 }));
+
+    assert_ok(record_spending(&fixture, &context, 600, None));
+    assert_eq!(
+        record_spending(&fixture, &context, 500, None),
+        Err(prism_error(PrismError::ExceedsEpochLimit))
+    );
+
+    harness::set_epoch(1);
+    assert_ok(record_spending(&fixture, &context, 500, None));
+    let state: ContextIdentity = harness::data(&context);
+    assert_eq!(state.epoch_spent, 500);
+    assert_eq!(state.total_spent, 1_100);
+}
+
+#[test]
+fn delegate_spends_but_cannot_manage_the_context() {
+    let fixture = setup();
+    let (context, result) = create_context(&fixture, 0);
+    assert_ok(result);
+    let delegate = wallet(Pubkey::new_unique());
+    assert_ok(context_instruction(
+        &fixture,
+        &context,
+        prism::instruction::SetContextDelegate { delegate: Some(*delegate.key) },
+    ));
+
+    assert_ok(record_spending_as(&delegate, &fixture, &context));
+    assert_eq!(harness::data::<ContextIdentity>(&context).total_spent, 100);
+
+    // The owner-gated paths, signed by the delegate instead
+    let as_delegate = Fixture { owner: delegate.clone(), ..fixture.clone() };
+    assert_eq!(refund(&as_delegate, &context, 100), Err(prism_error(PrismError::Unauthorized)));
+    assert_eq!(
+        context_instruction(&as_delegate, &context, prism::instruction::SetEpochLimit { max_per_epoch: 1 }),
+        Err(prism_error(PrismError::Unauthorized))
+    );
+
+    assert_ok(context_instruction(&fixture, &context, prism::instruction::SetContextDelegate { delegate: None }));
+    assert_eq!(record_spending_as(&delegate, &fixture, &context), Err(prism_error(PrismError::NotDelegate)));
+}
@@ -0,0 +1,204 @@
+//! Sub-contexts: spending roll-up, revocation and the root limits they share with contexts
+
+mod fixtures;
+mod harness;
+
+use anchor_lang::prelude::*;
+use anchor_lang::solana_program::entrypoint::ProgramResult;
+use anchor_lang::{system_program, InstructionData};
+use fixtures::{
+    checked, close_revoked_context, close_root_identity, context_args, create_context,
+    create_context_with, create_sub_context, prism_error, revoke_context, setup,
+    setup_with_max_contexts, with_cosigners, Fixture,
+};
+use harness::{assert_ok, process, program, readonly, START_TIME};
+use prism::{ContextIdentity, PrismError};
+
+fn record_sub_spending(
+    fixture: &Fixture,
+    parent: &AccountInfo<'static>,
+    sub_context: &AccountInfo<'static>,
+    amount: u64,
+) -> ProgramResult {
+    let result = process(
+        &prism::ID,
+        &with_cosigners(
+            fixture,
+            &[
+                fixture.owner.clone(),
+                fixture.root.clone(),
+                parent.clone(),
+                sub_context.clone(),
+                readonly(fixture.config.clone()),
+            ],
+        ),
+        &prism::instruction::RecordSubSpending {
+            amount,
+            auth_code: None,
+            expected_amount: None,
+            max_slippage_bps: None,
+            hook_data: None,
+            target_program: Pubkey::new_unique(),
+            nonce: 0,
+        }
+        .data(),
+    );
+    checked(result, &[parent, sub_context])
+}
+
+fn revoke_sub_context(
+    fixture: &Fixture,
+    parent: &AccountInfo<'static>,
+    sub_context: &AccountInfo<'static>,
+) -> ProgramResult {
+    let result = process(
+        &prism::ID,
+        &with_cosigners(
+            fixture,
+            &[fixture.owner.clone(), fixture.root.clone(), parent.clone(), sub_context.clone()],
+        ),
+        &prism::instruction::RevokeSubContext {}.data(),
+    );
+    checked(result, &[parent, sub_context])
+}
+
+fn revoke_and_close(fixture: &Fixture, context: &AccountInfo<'static>) -> ProgramResult {
+    let result = process(
+        &prism::ID,
+        &with_cosigners(
+            fixture,
+            &[
+                fixture.owner.clone(),
+                fixture.root.clone(),
+                context.clone(),
+                // No archive
+                program(prism::ID),
+                program(system_program::ID),
+                readonly(fixture.config.clone()),
+            ],
+        ),
+        &prism::instruction::RevokeAndClose { archive_on_close: false }.data(),
+    );
+    checked(result, &[context])
+}
+
+fn parent_with_child(fixture: &Fixture) -> (AccountInfo<'static>, AccountInfo<'static>) {
+    let (parent, result) = create_context(fixture, 0);
+    assert_ok(result);
+    let (sub_context, result) = create_sub_context(fixture, &parent);
+    assert_ok(result);
+    (parent, sub_context)
+}
+
+#[test]
+fn sub_spending_rolls_up_to_the_parent() {
+    let fixture = setup();
+    let (parent, sub_context) = parent_with_child(&fixture);
+
+    assert_ok(record_sub_spending(&fixture, &parent, &sub_context, 400));
+    assert_ok(record_sub_spending(&fixture, &parent, &sub_context, 100));
+
+    assert_eq!(harness::data::<ContextIdentity>(&sub_context).total_spent, 500);
+    assert_eq!(harness::data::<ContextIdentity>(&parent).total_spent, 500);
+}
+
+#[test]
+fn revoking_the_parent_cascades_to_its_sub_contexts() {
+    let fixture = setup();
+    let (parent, sub_context) = parent_with_child(&fixture);
+    assert_eq!(harness::data::<ContextIdentity>(&parent).live_children, 1);
+
+    assert_ok(revoke_context(&fixture, &parent, std::slice::from_ref(&sub_context)));
+
+    let parent_state: ContextIdentity = harness::data(&parent);
+    assert!(parent_state.revoked);
+    assert_eq!(parent_state.live_children, 0);
+    assert!(harness::data::<ContextIdentity>(&sub_context).revoked);
+}
+
+#[test]
+fn revoke_and_close_waits_for_live_sub_contexts() {
+    let fixture = setup();
+    let (parent, sub_context) = parent_with_child(&fixture);
+
+    assert_eq!(
+        revoke_and_close(&fixture, &parent),
+        Err(prism_error(PrismError::LiveSubContextsRemain))
+    );
+
+    assert_ok(revoke_sub_context(&fixture, &parent, &sub_context));
+    assert!(!harness::data::<ContextIdentity>(&parent).revoked);
+    assert_ok(revoke_and_close(&fixture, &parent));
+    assert_ok(close_revoked_context(&fixture, &sub_context));
+    assert_ok(close_root_identity(&fixture));
+}
+
+#[test]
+fn children_left_out_of_the_cascade_keep_the_parent_open() {
+    let fixture = setup();
+    let (parent, sub_context) = parent_with_child(&fixture);
+
+    assert_ok(revoke_context(&fixture, &parent, &[]));
+    assert_eq!(
+        close_revoked_context(&fixture, &parent),
+        Err(prism_error(PrismError::LiveSubContextsRemain))
+    );
+
+    assert_ok(revoke_sub_context(&fixture, &parent, &sub_context));
+    assert_eq!(
+        revoke_sub_context(&fixture, &parent, &sub_context),
+        Err(prism_error(PrismError::ContextAlreadyRevoked))
+    );
+    assert_ok(close_revoked_context(&fixture, &parent));
+    assert_ok(close_revoked_context(&fixture, &sub_context));
+    assert_ok(close_root_identity(&fixture));
+}
+
+#[test]
+fn sub_contexts_count_against_max_contexts() {
+    let fixture = setup_with_max_contexts(2);
+    let (parent, _sub_context) = parent_with_child(&fixture);
+
+    let (_, result) = create_sub_context(&fixture, &parent);
+    assert_eq!(result, Err(prism_error(PrismError::MaxContextsReached)));
+}
+
+#[test]
+fn sub_contexts_respect_the_creation_cooldown() {
+    let fixture = setup();
+    let (parent, result) = create_context(&fixture, 0);
+    assert_ok(result);
+    assert_ok(process(
+        &prism::ID,
+        &with_cosigners(
+            &fixture,
+            &[fixture.owner.clone(), fixture.root.clone(), readonly(fixture.config.clone())],
+        ),
+        &prism::instruction::SetCreationCooldown { creation_cooldown: 60 }.data(),
+    ));
+
+    let (_, result) = create_sub_context(&fixture, &parent);
+    assert_eq!(result, Err(prism_error(PrismError::CreationCooldownActive)));
+
+    harness::set_time(START_TIME + 60);
+    let (_, result) = create_sub_context(&fixture, &parent);
+    assert_ok(result);
+}
+
+#[test]
+fn sub_context_minimum_is_clamped_to_its_maximum() {
+    let fixture = setup();
+    let (parent, result) = create_context_with(
+        &fixture,
+        0,
+        prism::instruction::CreateContext { min_per_transaction: 5_000, ..context_args() },
+    );
+    assert_ok(result);
+
+    let (sub_context, result) = create_sub_context(&fixture, &parent);
+    assert_ok(result);
+
+    let child: ContextIdentity = harness::data(&sub_context);
+    assert_eq!(child.max_per_transaction, 1_000);
+    assert_eq!(child.min_per_transaction, 1_000);
+}